@@ -0,0 +1,57 @@
+use chain_builder::{ChainBuilder, Client, JoinMethods, QueryBuilder, WhereClauses};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::Value;
+
+// `depth` levels of `(a = ? OR (b = ? AND (...)))`
+fn nested_filter(qb: &mut QueryBuilder, depth: usize) {
+    qb.where_eq("a", Value::from(depth));
+    if depth == 0 {
+        return;
+    }
+    qb.or().where_subquery(|sub| {
+        sub.where_eq("b", Value::from(depth));
+        nested_filter(sub, depth - 1);
+    });
+}
+
+fn nested_builder(depth: usize) -> ChainBuilder {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| nested_filter(qb, depth));
+    builder
+}
+
+// wide upsert payload with a nested OR in the WHERE clause and join
+fn wide_update(columns: usize) -> ChainBuilder {
+    let mut data = serde_json::Map::new();
+    for i in 0..columns {
+        data.insert(format!("column_{}", i), Value::from(i));
+    }
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .update(Value::Object(data))
+        .query(|qb| {
+            qb.join("profiles", |join| {
+                join.on("profiles.user_id", "=", "users.id");
+                join.or().on("profiles.owner_id", "=", "users.id");
+            });
+            nested_filter(qb, 8);
+        });
+    builder
+}
+
+fn compile(c: &mut Criterion) {
+    for depth in [4, 16, 64] {
+        let builder = nested_builder(depth);
+        c.bench_function(&format!("nested_filter_depth_{}", depth), |b| {
+            b.iter(|| black_box(builder.build()))
+        });
+    }
+    let builder = wide_update(500);
+    c.bench_function("wide_update_500_columns", |b| {
+        b.iter(|| black_box(builder.build()))
+    });
+}
+
+criterion_group!(benches, compile);
+criterion_main!(benches);
@@ -0,0 +1,126 @@
+//! Aggregate functions with DISTINCT, per-aggregate FILTER and string aggregation
+//!
+//! FILTER is native on SQLite and emulated on MySQL by moving the condition
+//! into the argument (`SUM(CASE WHEN cond THEN x END)`), which aggregates
+//! skip because the non-matching rows become NULL.
+//!
+//! ```rust
+//! use chain_builder::{Aggregate, ChainBuilder, Client, Expr, Select};
+//! use serde_json::Value;
+//!
+//! let paid = Expr::col("status").eq(Value::from("paid").into());
+//! let mut builder = ChainBuilder::new(Client::Mysql);
+//! builder
+//!     .table("orders")
+//!     .select(Select::Columns(vec!["customer_id".into()]))
+//!     .select_aggregate(Aggregate::count("product_id").distinct(), Some("products"))
+//!     .select_aggregate(Aggregate::sum("total").filter(paid), Some("paid_total"));
+//!
+//! let (sql, _) = builder.to_sql();
+//! assert_eq!(
+//!     sql,
+//!     "SELECT customer_id, COUNT(DISTINCT `product_id`) AS products, SUM(CASE WHEN `status` = ? THEN `total` END) AS paid_total FROM orders"
+//! );
+//! ```
+
+use crate::expr::Expr;
+
+/// Aggregate function
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AggregateFunc {
+    /// COUNT
+    Count,
+    /// SUM
+    Sum,
+    /// AVG
+    Avg,
+    /// MIN
+    Min,
+    /// MAX
+    Max,
+    /// String aggregation with a separator (GROUP_CONCAT / group_concat)
+    StringAgg(String),
+}
+
+/// An aggregate call, usable in SELECT (`select_aggregate`), HAVING and ORDER BY
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Aggregate {
+    pub(crate) func: AggregateFunc,
+    /// `None` is `*` (COUNT only)
+    pub(crate) arg: Option<Expr>,
+    pub(crate) distinct: bool,
+    pub(crate) filter: Option<Expr>,
+}
+
+impl Aggregate {
+    /// Aggregate over an arbitrary expression
+    pub fn new(func: AggregateFunc, arg: Expr) -> Aggregate {
+        Aggregate {
+            func,
+            arg: Some(arg),
+            distinct: false,
+            filter: None,
+        }
+    }
+
+    /// COUNT(*)
+    pub fn count_all() -> Aggregate {
+        Aggregate {
+            func: AggregateFunc::Count,
+            arg: None,
+            distinct: false,
+            filter: None,
+        }
+    }
+
+    /// COUNT(column)
+    pub fn count(column: &str) -> Aggregate {
+        Aggregate::new(AggregateFunc::Count, Expr::col(column))
+    }
+
+    /// SUM(column)
+    pub fn sum(column: &str) -> Aggregate {
+        Aggregate::new(AggregateFunc::Sum, Expr::col(column))
+    }
+
+    /// AVG(column)
+    pub fn avg(column: &str) -> Aggregate {
+        Aggregate::new(AggregateFunc::Avg, Expr::col(column))
+    }
+
+    /// MIN(column)
+    pub fn min(column: &str) -> Aggregate {
+        Aggregate::new(AggregateFunc::Min, Expr::col(column))
+    }
+
+    /// MAX(column)
+    pub fn max(column: &str) -> Aggregate {
+        Aggregate::new(AggregateFunc::Max, Expr::col(column))
+    }
+
+    /// Concatenate the column's values with `separator`
+    pub fn string_agg(column: &str, separator: &str) -> Aggregate {
+        Aggregate::new(
+            AggregateFunc::StringAgg(separator.to_string()),
+            Expr::col(column),
+        )
+    }
+
+    /// Aggregate distinct values only
+    pub fn distinct(mut self) -> Aggregate {
+        self.distinct = true;
+        self
+    }
+
+    /// Only aggregate rows matching `condition`
+    pub fn filter(mut self, condition: impl Into<Expr>) -> Aggregate {
+        self.filter = Some(condition.into());
+        self
+    }
+}
+
+impl From<Aggregate> for Expr {
+    fn from(aggregate: Aggregate) -> Self {
+        Expr::Aggregate(Box::new(aggregate))
+    }
+}
@@ -1,7 +1,14 @@
 //! Main ChainBuilder implementation for building SQL queries
 
+use crate::aggregate::Aggregate;
+use crate::common::placeholder::named_binds;
+use crate::compiled::CompiledQuery;
+use crate::expr::{Case, Expr};
+use crate::policy::Trashed;
 use crate::query::QueryBuilder;
-use crate::types::{Client, Common, Method, Select};
+use crate::template::RejectParams;
+use crate::types::{Client, Common, Method, MissingColumn, RawBinds, Select};
+use crate::writer::SqlWriter;
 use serde_json::Value;
 
 /// Main query builder for constructing SQL queries
@@ -12,13 +19,13 @@ use serde_json::Value;
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ChainBuilder {
     /// Database client type
-    client: Client,
+    pub(crate) client: Client,
     /// Database name
     pub(crate) db: Option<String>,
     /// Table name
     pub(crate) table: Option<String>,
     /// Raw table expression with optional bind parameters
-    pub(crate) table_raw: Option<(String, Option<Vec<Value>>)>,
+    pub(crate) table_raw: Option<(String, RawBinds)>,
     /// Table alias
     pub(crate) as_name: Option<String>,
     /// SELECT clauses
@@ -33,6 +40,26 @@ pub struct ChainBuilder {
     pub(crate) sql_str: String,
     /// Whether to use DISTINCT
     pub(crate) is_distinct: bool,
+    /// Fill strategy for columns missing from `insert_many` rows
+    pub(crate) missing_column: MissingColumn,
+    /// Whether to skip rows that hit a duplicate key (INSERT IGNORE)
+    pub(crate) insert_ignore: bool,
+    /// Data for the upsert part of an INSERT
+    pub(crate) upsert: Option<Value>,
+    /// Columns and source query for INSERT ... SELECT
+    pub(crate) insert_select: Option<(Vec<String>, Box<ChainBuilder>)>,
+    /// Extra SET expressions for UPDATE (raw fragments or `column = expr`)
+    pub(crate) set_expr: Vec<Expr>,
+    /// Soft-delete column, enables the soft-delete scope
+    pub(crate) soft_delete: Option<String>,
+    /// Which rows the soft-delete scope lets through
+    pub(crate) trashed: Trashed,
+    /// Whether `delete()` really deletes under the soft-delete scope
+    pub(crate) force_delete: bool,
+    /// Tenant column and value enforced on every table reference
+    pub(crate) tenant: Option<(String, Value)>,
+    /// `where_in` lists longer than this compile to one JSON bind
+    pub(crate) in_list_threshold: Option<usize>,
 }
 
 impl ChainBuilder {
@@ -51,6 +78,16 @@ impl ChainBuilder {
             insert_update: Value::Null,
             sql_str: String::new(),
             is_distinct: false,
+            missing_column: MissingColumn::Null,
+            insert_ignore: false,
+            upsert: None,
+            insert_select: None,
+            set_expr: Vec::new(),
+            soft_delete: None,
+            trashed: Trashed::Without,
+            force_delete: false,
+            tenant: None,
+            in_list_threshold: None,
         }
     }
 
@@ -79,7 +116,17 @@ impl ChainBuilder {
 
     /// Set a raw table expression
     pub fn table_raw(&mut self, table: &str, val: Option<Vec<Value>>) -> &mut ChainBuilder {
-        self.table_raw = Some((table.to_string(), val));
+        self.table_raw = Some((table.to_string(), RawBinds::Positional(val)));
+        self
+    }
+
+    /// Set a raw table expression with `:name` binds
+    pub fn table_raw_named<'a>(
+        &mut self,
+        table: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    ) -> &mut ChainBuilder {
+        self.table_raw = Some((table.to_string(), RawBinds::Named(named_binds(binds))));
         self
     }
 
@@ -99,10 +146,46 @@ impl ChainBuilder {
     /// Add a raw SELECT expression
     pub fn select_raw(&mut self, sql: &str, binds: Option<Vec<Value>>) -> &mut ChainBuilder {
         self.method = Method::Select;
+        self.query.has_raw = true;
         self.select.push(Select::Raw(sql.to_string(), binds));
         self
     }
 
+    /// Start a CASE expression for this builder's client
+    pub fn case(&self) -> Case {
+        Case::new(self.client.clone())
+    }
+
+    /// Add a raw SELECT expression with `:name` binds
+    pub fn select_raw_named<'a>(
+        &mut self,
+        sql: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    ) -> &mut ChainBuilder {
+        self.method = Method::Select;
+        self.query.has_raw = true;
+        self.select
+            .push(Select::RawNamed(sql.to_string(), named_binds(binds)));
+        self
+    }
+
+    /// Add a typed SELECT expression with an optional alias
+    pub fn select_expr(&mut self, expr: Expr, alias: Option<&str>) -> &mut ChainBuilder {
+        self.method = Method::Select;
+        self.select
+            .push(Select::Expr(expr, alias.map(|a| a.to_string())));
+        self
+    }
+
+    /// Add an aggregate (COUNT, SUM, string aggregation, ...) with an optional alias
+    pub fn select_aggregate(
+        &mut self,
+        aggregate: Aggregate,
+        alias: Option<&str>,
+    ) -> &mut ChainBuilder {
+        self.select_expr(aggregate.into(), alias)
+    }
+
     /// Add DISTINCT SELECT
     pub fn select_distinct(&mut self, columns: Vec<String>) -> &mut ChainBuilder {
         self.method = Method::Select;
@@ -163,16 +246,38 @@ impl ChainBuilder {
     pub fn insert(&mut self, data: Value) -> &mut ChainBuilder {
         self.method = Method::Insert;
         self.insert_update = data;
+        self.insert_select = None;
         self
     }
 
     /// Set INSERT multiple rows data
     pub fn insert_many(&mut self, data: Vec<Value>) -> &mut ChainBuilder {
+        if data.is_empty() {
+            panic!("[Err] insert_many: no rows to insert");
+        }
         self.method = Method::InsertMany;
         self.insert_update = Value::Array(data);
         self
     }
 
+    /// Set how `insert_many` fills columns missing from a row
+    pub fn missing_column(&mut self, fill: MissingColumn) -> &mut ChainBuilder {
+        self.missing_column = fill;
+        self
+    }
+
+    /// Compile `where_in` / `where_not_in` lists longer than `threshold` as one JSON bind
+    ///
+    /// MySQL 8 reads the list with `JSON_TABLE`, SQLite with `json_each`, so large lists
+    /// stay clear of the placeholder limit and share one prepared statement. On MySQL
+    /// only integer lists are rewritten: `JSON_TABLE` strings are `utf8mb4_bin`, and
+    /// comparing them to a column with another collation fails with "Illegal mix of
+    /// collations". Nested builders inherit the threshold unless they set their own.
+    pub fn in_list_threshold(&mut self, threshold: usize) -> &mut ChainBuilder {
+        self.in_list_threshold = Some(threshold);
+        self
+    }
+
     /// Set UPDATE data
     pub fn update(&mut self, data: Value) -> &mut ChainBuilder {
         self.method = Method::Update;
@@ -180,47 +285,68 @@ impl ChainBuilder {
         self
     }
 
-    /// Add INSERT IGNORE (MySQL)
+    /// Set INSERT ... SELECT with the rows produced by another query
+    pub fn insert_from(&mut self, columns: Vec<String>, source: ChainBuilder) -> &mut ChainBuilder {
+        self.method = Method::Insert;
+        self.insert_select = Some((columns, Box::new(source)));
+        self
+    }
+
+    /// Add INSERT IGNORE (MySQL) / INSERT OR IGNORE (SQLite)
     pub fn insert_ignore(&mut self, data: Value) -> &mut ChainBuilder {
         self.method = Method::Insert;
         self.insert_update = data;
-        // Note: This will need special handling in the SQL compiler
+        self.insert_select = None;
+        self.insert_ignore = true;
         self
     }
 
     /// Add UPSERT (INSERT ... ON DUPLICATE KEY UPDATE)
-    pub fn insert_or_update(&mut self, data: Value, _update_data: Value) -> &mut ChainBuilder {
+    pub fn insert_or_update(&mut self, data: Value, update_data: Value) -> &mut ChainBuilder {
         self.method = Method::Insert;
         self.insert_update = data;
-        // Note: This will need special handling in the SQL compiler
+        self.insert_select = None;
+        self.upsert = Some(update_data);
         self
     }
 
-    /// Add raw UPDATE statement
-    pub fn update_raw(&mut self, _sql: &str, _binds: Option<Vec<Value>>) -> &mut ChainBuilder {
+    /// Skip rows that hit a duplicate key for the current INSERT
+    pub fn ignore(&mut self) -> &mut ChainBuilder {
+        self.insert_ignore = true;
+        self
+    }
+
+    /// Update these columns when the current INSERT hits a duplicate key
+    ///
+    /// Renders as `ON DUPLICATE KEY UPDATE` on MySQL and
+    /// `ON CONFLICT DO UPDATE SET` on SQLite.
+    pub fn upsert(&mut self, update_data: Value) -> &mut ChainBuilder {
+        self.upsert = Some(update_data);
+        self
+    }
+
+    /// Add a raw SET expression to an UPDATE, e.g. `"views = views + ?"`
+    pub fn update_raw(&mut self, sql: &str, binds: Option<Vec<Value>>) -> &mut ChainBuilder {
         self.method = Method::Update;
-        // Note: This will need special handling in the SQL compiler
+        self.set_expr.push(Expr::Raw(sql.to_string(), binds));
         self
     }
 
-    /// Increment a column value
-    pub fn increment(&mut self, column: &str, amount: i64) -> &mut ChainBuilder {
+    /// Add `column = expr` to an UPDATE
+    pub fn update_expr(&mut self, column: &str, expr: Expr) -> &mut ChainBuilder {
         self.method = Method::Update;
-        let update_data = serde_json::json!({
-            column: format!("{} + {}", column, amount)
-        });
-        self.insert_update = update_data;
+        self.set_expr.push(Expr::col(column).eq(expr));
         self
     }
 
+    /// Increment a column value
+    pub fn increment(&mut self, column: &str, amount: i64) -> &mut ChainBuilder {
+        self.update_expr(column, Expr::col(column).add(Expr::val(amount)))
+    }
+
     /// Decrement a column value
     pub fn decrement(&mut self, column: &str, amount: i64) -> &mut ChainBuilder {
-        self.method = Method::Update;
-        let update_data = serde_json::json!({
-            column: format!("{} - {}", column, amount)
-        });
-        self.insert_update = update_data;
-        self
+        self.update_expr(column, Expr::col(column).sub(Expr::val(amount)))
     }
 
     /// Set DELETE operation
@@ -271,31 +397,245 @@ impl ChainBuilder {
         self
     }
 
+    /// Database client type
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Database name, if set
+    pub fn db_name(&self) -> Option<&str> {
+        self.db.as_deref()
+    }
+
+    /// Table name, if set
+    pub fn table_name(&self) -> Option<&str> {
+        self.table.as_deref()
+    }
+
+    /// Table alias, if set
+    pub fn alias(&self) -> Option<&str> {
+        self.as_name.as_deref()
+    }
+
+    /// SELECT clauses
+    pub fn selects(&self) -> &[Select] {
+        &self.select
+    }
+
+    /// Mutable SELECT clauses
+    pub fn selects_mut(&mut self) -> &mut Vec<Select> {
+        &mut self.select
+    }
+
+    /// Query parts (WHERE, JOIN, etc.)
+    pub fn query_builder(&self) -> &QueryBuilder {
+        &self.query
+    }
+
+    /// Mutable query parts (WHERE, JOIN, etc.)
+    pub fn query_builder_mut(&mut self) -> &mut QueryBuilder {
+        &mut self.query
+    }
+
+    /// SQL operation method
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// INSERT/UPDATE payload
+    pub fn data(&self) -> &Value {
+        &self.insert_update
+    }
+
+    /// Mutable INSERT/UPDATE payload
+    pub fn data_mut(&mut self) -> &mut Value {
+        &mut self.insert_update
+    }
+
+    /// Remove the SELECT list and DISTINCT, selecting `*` again
+    pub fn clear_select(&mut self) -> &mut ChainBuilder {
+        self.select.clear();
+        self.is_distinct = false;
+        self
+    }
+
+    /// Replace the SELECT list with a single clause
+    pub fn replace_select(&mut self, select: Select) -> &mut ChainBuilder {
+        self.clear_select().select(select)
+    }
+
+    /// Remove all WHERE conditions
+    pub fn clear_where(&mut self) -> &mut ChainBuilder {
+        self.query.statement.clear();
+        self
+    }
+
+    /// Remove all JOIN clauses
+    pub fn clear_joins(&mut self) -> &mut ChainBuilder {
+        self.query.join.clear();
+        self
+    }
+
+    /// Remove ORDER BY clauses (structured and raw)
+    pub fn clear_order_by(&mut self) -> &mut ChainBuilder {
+        self.query.query_common.retain(|c| {
+            !matches!(
+                c,
+                Common::OrderBy(..)
+                    | Common::OrderByRaw(..)
+                    | Common::OrderByNulls(..)
+                    | Common::OrderByExpr(..)
+            )
+        });
+        self
+    }
+
+    /// Remove GROUP BY clauses (structured and raw)
+    pub fn clear_group_by(&mut self) -> &mut ChainBuilder {
+        self.query.query_common.retain(|c| {
+            !matches!(
+                c,
+                Common::GroupBy(_) | Common::GroupByRaw(..) | Common::GroupByRollup(_)
+            )
+        });
+        self
+    }
+
+    /// Remove HAVING conditions
+    pub fn clear_having(&mut self) -> &mut ChainBuilder {
+        self.query.query_common.retain(|c| {
+            !matches!(
+                c,
+                Common::Having(..) | Common::HavingNamed(..) | Common::HavingExpr(_)
+            )
+        });
+        self
+    }
+
+    /// Remove LIMIT and OFFSET
+    pub fn clear_limit_offset(&mut self) -> &mut ChainBuilder {
+        self.query
+            .query_common
+            .retain(|c| !matches!(c, Common::Limit(_) | Common::Offset(_)));
+        self
+    }
+
+    /// Remove UNION clauses
+    pub fn clear_union(&mut self) -> &mut ChainBuilder {
+        self.query
+            .query_common
+            .retain(|c| !matches!(c, Common::Union(..)));
+        self
+    }
+
+    /// Remove raw SQL added with `add_raw`
+    pub fn clear_raw(&mut self) -> &mut ChainBuilder {
+        self.query.raw.clear();
+        self
+    }
+
     /// Configure query parts (WHERE, JOIN, etc.)
     pub fn query(&mut self, query: impl FnOnce(&mut QueryBuilder)) {
         query(&mut self.query);
     }
 
+    /// AND the conditions of another query builder: `(self) AND (other)`
+    ///
+    /// Joins, raw SQL and common clauses of `other` are merged as well;
+    /// identical joins are kept once and a conflicting join on the same
+    /// table and alias panics.
+    pub fn and_where_builder(&mut self, query_builder: QueryBuilder) -> &mut ChainBuilder {
+        self.query.merge(query_builder);
+        self
+    }
+
+    /// OR the conditions of another query builder: `(self) OR (other)`
+    pub fn or_where_builder(&mut self, query_builder: QueryBuilder) -> &mut ChainBuilder {
+        self.query.or_merge(query_builder);
+        self
+    }
+
     /// Add raw SQL
     pub fn add_raw(&mut self, sql: &str, val: Option<Vec<Value>>) {
-        self.query.raw.push((sql.to_string(), val));
+        self.query
+            .raw
+            .push((sql.to_string(), RawBinds::Positional(val)));
+    }
+
+    /// Add raw SQL with `:name` binds
+    pub fn add_raw_named<'a>(
+        &mut self,
+        sql: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    ) {
+        self.query
+            .raw
+            .push((sql.to_string(), RawBinds::Named(named_binds(binds))));
     }
 
     /// Generate SQL string and bind parameters
     pub fn to_sql(&mut self) -> (String, Vec<Value>) {
-        match self.client {
+        let rs = self.compile();
+        self.sql_str = rs.0.clone();
+        rs
+    }
+
+    /// Compile into an owned `CompiledQuery`, leaving the builder untouched
+    pub fn build(&self) -> CompiledQuery {
+        self.build_for(self.client.clone())
+    }
+
+    /// Generate SQL for another dialect, without changing the builder
+    ///
+    /// The whole tree (subqueries, EXISTS, CTEs, unions) is rendered for
+    /// `client`, whatever client the builder was created with.
+    pub fn to_sql_for(&self, client: Client) -> (String, Vec<Value>) {
+        self.compile_for(client)
+    }
+
+    /// `build()` for another dialect
+    pub fn build_for(&self, client: Client) -> CompiledQuery {
+        let (sql, binds) = self.compile_for(client.clone());
+        CompiledQuery {
+            sql,
+            binds,
+            dialect: client,
+        }
+    }
+
+    fn compile(&self) -> (String, Vec<Value>) {
+        self.compile_for(self.client.clone())
+    }
+
+    fn compile_for(&self, client: Client) -> (String, Vec<Value>) {
+        let mut compiled = (String::new(), vec![]);
+        self.write_sql_for(client, &mut compiled);
+        compiled
+    }
+
+    /// Compile straight into a `SqlWriter`
+    pub fn write_sql(&self, writer: &mut dyn SqlWriter) {
+        self.write_sql_for(self.client.clone(), writer)
+    }
+
+    /// `write_sql()` for another dialect
+    ///
+    /// Panics on a template `Param`, which only `to_template()` can fill.
+    pub fn write_sql_for(&self, client: Client, writer: &mut dyn SqlWriter) {
+        self.write_template_sql(client, &mut RejectParams(writer));
+    }
+
+    // Compile with `Param` markers bound as-is, for `QueryTemplate`
+    pub(crate) fn write_template_sql(&self, client: Client, writer: &mut dyn SqlWriter) {
+        let scoped = crate::policy::apply(self);
+        let chain_builder = scoped.as_ref().unwrap_or(self);
+        let (sql, binds) = crate::writer::size_hint(chain_builder);
+        writer.reserve(sql, binds);
+        match client {
             #[cfg(feature = "mysql")]
-            Client::Mysql => {
-                let rs = crate::mysql::merge_to_sql(crate::mysql::to_sql(self));
-                self.sql_str = rs.0.clone();
-                (self.sql_str.clone(), rs.1)
-            }
+            Client::Mysql => crate::mysql::write_sql(chain_builder, writer),
             #[cfg(feature = "sqlite")]
-            Client::Sqlite => {
-                let rs = crate::sqlite::merge_to_sql(crate::sqlite::to_sql(self));
-                self.sql_str = rs.0.clone();
-                (self.sql_str.clone(), rs.1)
-            }
+            Client::Sqlite => crate::sqlite::write_sql(chain_builder, writer),
             #[cfg(feature = "postgres")]
             Client::Postgres => {
                 panic!("PostgreSQL support not yet implemented");
@@ -0,0 +1,191 @@
+// Clauses shared by every dialect; each dialect decides their order
+use super::{
+    expr_compiler::{compile_expr, is_condition_list},
+    list_compiler::{push_columns, push_list_separator, push_order_by_nulls},
+    method_compiler::ToSqlProvider,
+    placeholder::{write_named, write_raw, write_raw_binds},
+};
+use crate::{
+    builder::ChainBuilder,
+    expr::{BinaryOp, Expr},
+    types::{Client, Common},
+    writer::SqlWriter,
+};
+
+// `WITH [RECURSIVE] a AS (...), b AS (...) `
+pub fn with_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let commons = &chain_builder.query.query_common;
+    if !commons.iter().any(|c| matches!(c, Common::With(..))) {
+        return;
+    }
+    writer.push_sql("WITH ");
+    // RECURSIVE applies to the whole WITH list
+    if commons
+        .iter()
+        .any(|c| matches!(c, Common::With(_, true, _)))
+    {
+        writer.push_sql("RECURSIVE ");
+    }
+    let mut is_first = true;
+    for common in commons.iter() {
+        if let Common::With(alias, _, sub_builder) = common {
+            if is_first {
+                is_first = false;
+            } else {
+                writer.push_sql(", ");
+            }
+            writer.push_sql(alias);
+            writer.push_sql(" AS (");
+            to_sql_provider.write_sql(sub_builder, writer);
+            writer.push_sql(")");
+        }
+    }
+    writer.push_sql(" ");
+}
+
+// ` GROUP BY ...` in call order; `with_rollup` appends ` WITH ROLLUP` when a rollup is present
+pub fn group_by_compiler(
+    chain_builder: &ChainBuilder,
+    with_rollup: bool,
+    client: &Client,
+    writer: &mut dyn SqlWriter,
+) {
+    let mut is_first = true;
+    let mut rollup = false;
+    for common in chain_builder.query.query_common.iter() {
+        match common {
+            Common::GroupBy(columns) => {
+                push_list_separator(writer, &mut is_first, " GROUP BY ");
+                push_columns(writer, columns);
+            }
+            Common::GroupByRollup(columns) if with_rollup => {
+                push_list_separator(writer, &mut is_first, " GROUP BY ");
+                push_columns(writer, columns);
+                rollup = true;
+            }
+            Common::GroupByRaw(sql, binds) => {
+                push_list_separator(writer, &mut is_first, " GROUP BY ");
+                write_raw(writer, client, sql, binds.as_deref());
+            }
+            _ => {}
+        }
+    }
+    if rollup {
+        writer.push_sql(" WITH ROLLUP");
+    }
+}
+
+// ` HAVING a AND b`
+pub fn having_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let mut is_first = true;
+    for common in chain_builder.query.query_common.iter() {
+        if !matches!(
+            common,
+            Common::Having(..) | Common::HavingNamed(..) | Common::HavingExpr(_)
+        ) {
+            continue;
+        }
+        if is_first {
+            is_first = false;
+            writer.push_sql(" HAVING ");
+        } else {
+            writer.push_sql(" AND ");
+        }
+        match common {
+            Common::Having(sql, binds) => {
+                write_raw(writer, &to_sql_provider.client(), sql, binds.as_deref())
+            }
+            Common::HavingNamed(sql, values) => {
+                write_named(writer, &to_sql_provider.client(), sql, values)
+            }
+            // keep `a OR b` from leaking into the surrounding AND list
+            Common::HavingExpr(expr)
+                if matches!(expr, Expr::Binary(_, BinaryOp::Or, _)) || is_condition_list(expr) =>
+            {
+                writer.push_sql("(");
+                compile_expr(expr, to_sql_provider, writer);
+                writer.push_sql(")");
+            }
+            Common::HavingExpr(expr) => compile_expr(expr, to_sql_provider, writer),
+            _ => {}
+        }
+    }
+}
+
+// ` ORDER BY ...` in call order
+pub fn order_by_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let mut is_first = true;
+    for common in chain_builder.query.query_common.iter() {
+        match common {
+            Common::OrderBy(column, order) => {
+                push_list_separator(writer, &mut is_first, " ORDER BY ");
+                writer.push_sql(column);
+                writer.push_sql(" ");
+                writer.push_sql(order);
+            }
+            Common::OrderByRaw(sql, binds) => {
+                push_list_separator(writer, &mut is_first, " ORDER BY ");
+                write_raw(writer, &to_sql_provider.client(), sql, binds.as_deref());
+            }
+            Common::OrderByNulls(column, order, nulls) => {
+                push_list_separator(writer, &mut is_first, " ORDER BY ");
+                push_order_by_nulls(writer, to_sql_provider.client(), column, order, nulls);
+            }
+            Common::OrderByExpr(expr, order) => {
+                push_list_separator(writer, &mut is_first, " ORDER BY ");
+                compile_expr(expr, to_sql_provider, writer);
+                writer.push_sql(" ");
+                writer.push_sql(order);
+            }
+            _ => {}
+        }
+    }
+}
+
+// The last LIMIT and OFFSET set
+pub fn limit_offset(chain_builder: &ChainBuilder) -> (Option<usize>, Option<usize>) {
+    let mut limit = None;
+    let mut offset = None;
+    for common in chain_builder.query.query_common.iter() {
+        match common {
+            Common::Limit(l) => limit = Some(*l),
+            Common::Offset(o) => offset = Some(*o),
+            _ => {}
+        }
+    }
+    (limit, offset)
+}
+
+// ` UNION [ALL] ...` for every union
+pub fn union_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    for common in chain_builder.query.query_common.iter() {
+        if let Common::Union(is_all, sub_builder) = common {
+            writer.push_sql(if *is_all { " UNION ALL " } else { " UNION " });
+            to_sql_provider.write_sql(sub_builder, writer);
+        }
+    }
+}
+
+// ` raw raw ...` from `add_raw`
+pub fn raw_compiler(chain_builder: &ChainBuilder, client: &Client, writer: &mut dyn SqlWriter) {
+    for (sql, binds) in chain_builder.query.raw.iter() {
+        writer.push_sql(" ");
+        write_raw_binds(writer, client, sql, binds);
+    }
+}
@@ -0,0 +1,222 @@
+use super::method_compiler::ToSqlProvider;
+use super::placeholder::write_raw;
+use super::statement_compiler::compile_statements;
+use crate::aggregate::{Aggregate, AggregateFunc};
+use crate::debug::quote_string;
+use crate::expr::{BinaryOp, Expr};
+use crate::types::Client;
+use crate::writer::SqlWriter;
+use serde_json::Value;
+
+// `users.name` -> `users`.`name` (MySQL) / "users"."name" (SQLite); `*` stays bare
+pub fn quote_column(client: Client, column: &str, writer: &mut dyn SqlWriter) {
+    let (quote, escaped_quote) = match client {
+        Client::Mysql => ("`", "``"),
+        _ => ("\"", "\"\""),
+    };
+    for (i, part) in column.split('.').enumerate() {
+        if i > 0 {
+            writer.push_sql(".");
+        }
+        if part == "*" {
+            writer.push_sql(part);
+            continue;
+        }
+        writer.push_sql(quote);
+        for (j, piece) in part.split(quote).enumerate() {
+            if j > 0 {
+                writer.push_sql(escaped_quote);
+            }
+            writer.push_sql(piece);
+        }
+        writer.push_sql(quote);
+    }
+}
+
+// Binding strength, higher binds tighter
+fn precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => 1,
+        BinaryOp::And => 2,
+        BinaryOp::Eq
+        | BinaryOp::Ne
+        | BinaryOp::Lt
+        | BinaryOp::Lte
+        | BinaryOp::Gt
+        | BinaryOp::Gte
+        | BinaryOp::Like => 3,
+        BinaryOp::Add | BinaryOp::Sub => 4,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 5,
+    }
+}
+
+// A condition of several statements must be parenthesised inside another expression
+pub(crate) fn is_condition_list(expr: &Expr) -> bool {
+    matches!(expr, Expr::Condition(query_builder) if query_builder.statement.len() > 1)
+}
+
+fn compile_operand<T: ToSqlProvider>(
+    expr: &Expr,
+    needs_parens: bool,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    if needs_parens {
+        writer.push_sql("(");
+        compile_expr(expr, to_sql_provider, writer);
+        writer.push_sql(")");
+    } else {
+        compile_expr(expr, to_sql_provider, writer);
+    }
+}
+
+pub(crate) fn compile_expr<T: ToSqlProvider>(
+    expr: &Expr,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    match expr {
+        Expr::Column(column) => quote_column(to_sql_provider.client(), column, writer),
+        Expr::Value(value) => writer.push_bind(value.clone()),
+        Expr::Raw(raw, raw_binds) => {
+            write_raw(writer, &to_sql_provider.client(), raw, raw_binds.as_deref());
+        }
+        Expr::Function(name, args) => {
+            writer.push_sql(name);
+            writer.push_sql("(");
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    writer.push_sql(", ");
+                }
+                compile_expr(arg, to_sql_provider, writer);
+            }
+            writer.push_sql(")");
+        }
+        Expr::Binary(lhs, op, rhs) => {
+            let level = precedence(*op);
+            // NOT binds looser than comparisons: (NOT a) = b, not NOT (a = b)
+            let not_parens = level > precedence(BinaryOp::And);
+            let lhs_parens = matches!(**lhs, Expr::Binary(_, l, _) if precedence(l) < level)
+                || matches!(**lhs, Expr::Not(_)) && not_parens
+                || is_condition_list(lhs);
+            // a - (b - c): equal precedence on the right is not associative
+            let rhs_parens = matches!(**rhs, Expr::Binary(_, r, _) if precedence(r) <= level)
+                || matches!(**rhs, Expr::Not(_)) && not_parens
+                || is_condition_list(rhs);
+            compile_operand(lhs, lhs_parens, to_sql_provider, writer);
+            writer.push_sql(" ");
+            writer.push_sql(op.as_sql());
+            writer.push_sql(" ");
+            compile_operand(rhs, rhs_parens, to_sql_provider, writer);
+        }
+        Expr::Not(inner) => {
+            writer.push_sql("NOT ");
+            let parens =
+                matches!(**inner, Expr::Binary(..) | Expr::IsNull(..)) || is_condition_list(inner);
+            compile_operand(inner, parens, to_sql_provider, writer);
+        }
+        Expr::IsNull(inner, is_not) => {
+            let parens =
+                matches!(**inner, Expr::Binary(..) | Expr::Not(_)) || is_condition_list(inner);
+            compile_operand(inner, parens, to_sql_provider, writer);
+            writer.push_sql(if *is_not { " IS NOT NULL" } else { " IS NULL" });
+        }
+        Expr::Case(whens, else_) => {
+            writer.push_sql("CASE");
+            for (condition, result) in whens {
+                writer.push_sql(" WHEN ");
+                compile_expr(condition, to_sql_provider, writer);
+                writer.push_sql(" THEN ");
+                compile_expr(result, to_sql_provider, writer);
+            }
+            if let Some(else_) = else_ {
+                writer.push_sql(" ELSE ");
+                compile_expr(else_, to_sql_provider, writer);
+            }
+            writer.push_sql(" END");
+        }
+        Expr::Cast(inner, sql_type) => {
+            writer.push_sql("CAST(");
+            compile_expr(inner, to_sql_provider, writer);
+            writer.push_sql(" AS ");
+            writer.push_sql(sql_type);
+            writer.push_sql(")");
+        }
+        Expr::Condition(query_builder) => {
+            compile_statements(&query_builder.statement, " AND ", to_sql_provider, writer);
+        }
+        Expr::Grouping(column) => {
+            // the SQLite rollup emulation replaces GROUPING() with 0/1 per branch
+            if matches!(to_sql_provider.client(), Client::Sqlite) {
+                panic!("[Err] grouping: GROUPING() needs group_by_rollup on SQLite");
+            }
+            writer.push_sql("GROUPING(");
+            quote_column(to_sql_provider.client(), column, writer);
+            writer.push_sql(")");
+        }
+        Expr::Aggregate(aggregate) => compile_aggregate(aggregate, to_sql_provider, writer),
+        Expr::Subquery(chain_builder) => {
+            writer.push_sql("(");
+            to_sql_provider.write_sql(chain_builder, writer);
+            writer.push_sql(")");
+        }
+    }
+}
+
+// FILTER is native outside MySQL; MySQL gets `FUNC(CASE WHEN filter THEN arg END)`
+fn compile_aggregate<T: ToSqlProvider>(
+    aggregate: &Aggregate,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let is_mysql = matches!(to_sql_provider.client(), Client::Mysql);
+    if aggregate.distinct && aggregate.arg.is_none() {
+        panic!("[Err] aggregate: DISTINCT needs a column, not *");
+    }
+    writer.push_sql(match &aggregate.func {
+        AggregateFunc::Count => "COUNT",
+        AggregateFunc::Sum => "SUM",
+        AggregateFunc::Avg => "AVG",
+        AggregateFunc::Min => "MIN",
+        AggregateFunc::Max => "MAX",
+        AggregateFunc::StringAgg(_) if is_mysql => "GROUP_CONCAT",
+        AggregateFunc::StringAgg(_) => "group_concat",
+    });
+    writer.push_sql("(");
+    if aggregate.distinct {
+        writer.push_sql("DISTINCT ");
+    }
+    match (&aggregate.filter, &aggregate.arg) {
+        (Some(filter), arg) if is_mysql => {
+            writer.push_sql("CASE WHEN ");
+            compile_expr(filter, to_sql_provider, writer);
+            writer.push_sql(" THEN ");
+            match arg {
+                Some(arg) => compile_expr(arg, to_sql_provider, writer),
+                None => writer.push_sql("1"),
+            }
+            writer.push_sql(" END");
+        }
+        (_, Some(arg)) => compile_expr(arg, to_sql_provider, writer),
+        (_, None) => writer.push_sql("*"),
+    }
+    if let AggregateFunc::StringAgg(separator) = &aggregate.func {
+        if is_mysql {
+            // MySQL only accepts a string literal after SEPARATOR, not a placeholder
+            writer.push_sql(" SEPARATOR ");
+            writer.push_sql(&quote_string(&Client::Mysql, separator));
+        } else if !aggregate.distinct {
+            writer.push_sql(", ");
+            writer.push_bind(Value::String(separator.clone()));
+        } else if separator != "," {
+            // SQLite DISTINCT aggregates take exactly one argument (separator is always ",")
+            panic!("[Err] aggregate: SQLite group_concat(DISTINCT ..) only supports the \",\" separator");
+        }
+    }
+    writer.push_sql(")");
+    if let Some(filter) = aggregate.filter.as_ref().filter(|_| !is_mysql) {
+        writer.push_sql(" FILTER (WHERE ");
+        compile_expr(filter, to_sql_provider, writer);
+        writer.push_sql(")");
+    }
+}
@@ -1,67 +1,90 @@
-use crate::{builder::ChainBuilder, query::join::JoinStatement};
-use serde_json::Value;
-
-pub fn join_compiler(chain_builder: &ChainBuilder, prefix: bool) -> (String, Vec<Value>) {
-    let mut to_sql_str = String::new();
-    let mut to_binds: Vec<serde_json::Value> = vec![];
-    for (i, join) in chain_builder.query.join.iter().enumerate() {
-        if i > 0 {
-            to_sql_str.push(' ');
-        }
+use super::placeholder::{write_raw, write_raw_binds};
+use crate::{builder::ChainBuilder, query::join::JoinStatement, types::Client, writer::SqlWriter};
 
+// ` JOIN ...` for every join
+pub fn join_compiler(chain_builder: &ChainBuilder, client: &Client, writer: &mut dyn SqlWriter) {
+    for join in chain_builder.query.join.iter() {
+        writer.push_sql(" ");
         if let Some((raw, binds)) = &join.raw {
-            to_sql_str.push_str(raw);
-            to_binds.extend(binds.clone().unwrap_or_default());
+            write_raw_binds(writer, client, raw, binds);
+            continue;
+        }
+        if let Some(columns) = &join.using {
+            writer.push_sql(&join.join_type);
+            writer.push_sql(" ");
+            writer.push_sql(&join.table);
+            writer.push_sql(" USING (");
+            writer.push_sql(&columns.join(", "));
+            writer.push_sql(")");
             continue;
         }
 
-        if prefix {
-            let table = if let Some(db) = &chain_builder.db {
-                format!("{}.{}", db, join.table)
-            } else {
-                join.table.clone()
-            };
-            if let Some(as_name) = &join.as_name {
-                to_sql_str.push_str(&format!("{} {} as {} ON ", join.join_type, table, as_name));
-            } else {
-                to_sql_str.push_str(&format!("{} {} ON ", join.join_type, table));
-            }
+        writer.push_sql(&join.join_type);
+        writer.push_sql(" ");
+        if let Some(db) = &chain_builder.db {
+            writer.push_sql(db);
+            writer.push_sql(".");
+        }
+        writer.push_sql(&join.table);
+        if let Some(as_name) = &join.as_name {
+            writer.push_sql(" as ");
+            writer.push_sql(as_name);
         }
+        writer.push_sql(" ON ");
+        compile_join_statements(&join.statement, client, writer);
+    }
+}
 
-        for (j, statement) in join.statement.iter().enumerate() {
-            match statement {
-                JoinStatement::On(column, operator, column2) => {
-                    if j > 0 {
-                        to_sql_str.push_str(" AND ");
-                    }
-                    to_sql_str.push_str(format!("{} {} {}", column, operator, column2).as_str());
+// Write ON conditions, nested OR / sub chains write into the same writer
+fn compile_join_statements(
+    statements: &[JoinStatement],
+    client: &Client,
+    writer: &mut dyn SqlWriter,
+) {
+    for (j, statement) in statements.iter().enumerate() {
+        match statement {
+            JoinStatement::On(column, operator, column2) => {
+                if j > 0 {
+                    writer.push_sql(" AND ");
+                }
+                writer.push_sql(column);
+                writer.push_sql(" ");
+                writer.push_sql(operator);
+                writer.push_sql(" ");
+                writer.push_sql(column2);
+            }
+            JoinStatement::OrChain(qb) => {
+                if j > 0 {
+                    writer.push_sql(" OR ");
                 }
-                JoinStatement::OrChain(qb) => {
-                    if j > 0 {
-                        to_sql_str.push_str(" OR ");
-                    }
-                    let mut c = chain_builder.clone();
-                    c.query.join = vec![*qb.clone()];
-                    let sql = join_compiler(&c, false);
-                    to_sql_str.push_str(&format!("({})", sql.0));
-                    to_binds.extend(sql.1);
+                writer.push_sql("(");
+                compile_join_statements(&qb.statement, client, writer);
+                writer.push_sql(")");
+            }
+            JoinStatement::SubChain(qb) => {
+                if j > 0 {
+                    writer.push_sql(" AND ");
                 }
-                JoinStatement::OnVal(column, operator, value) => {
-                    if j > 0 {
-                        to_sql_str.push_str(" AND ");
-                    }
-                    to_sql_str.push_str(format!("{} {} ?", column, operator).as_str());
-                    to_binds.push(value.clone());
+                writer.push_sql("(");
+                compile_join_statements(&qb.statement, client, writer);
+                writer.push_sql(")");
+            }
+            JoinStatement::OnVal(column, operator, value) => {
+                if j > 0 {
+                    writer.push_sql(" AND ");
                 }
-                JoinStatement::OnRaw(raw, binds) => {
-                    if j > 0 {
-                        to_sql_str.push_str(" AND ");
-                    }
-                    to_sql_str.push_str(raw);
-                    to_binds.extend(binds.clone().unwrap_or_default());
+                writer.push_sql(column);
+                writer.push_sql(" ");
+                writer.push_sql(operator);
+                writer.push_sql(" ");
+                writer.push_bind(value.clone());
+            }
+            JoinStatement::OnRaw(raw, raw_binds) => {
+                if j > 0 {
+                    writer.push_sql(" AND ");
                 }
+                write_raw(writer, client, raw, raw_binds.as_deref());
             }
         }
     }
-    (to_sql_str, to_binds)
 }
@@ -0,0 +1,48 @@
+use crate::types::{Client, NullsOrder};
+use crate::writer::SqlWriter;
+
+// Start a comma-separated clause (GROUP BY / ORDER BY) with `keyword`, or continue it
+pub fn push_list_separator(writer: &mut dyn SqlWriter, is_first: &mut bool, keyword: &str) {
+    if *is_first {
+        *is_first = false;
+        writer.push_sql(keyword);
+    } else {
+        writer.push_sql(", ");
+    }
+}
+
+// `a, b, c`
+pub fn push_columns(writer: &mut dyn SqlWriter, columns: &[String]) {
+    for (i, column) in columns.iter().enumerate() {
+        if i > 0 {
+            writer.push_sql(", ");
+        }
+        writer.push_sql(column);
+    }
+}
+
+// `column order NULLS FIRST|LAST`; MySQL has no NULLS clause, so sort on `column IS NULL` first
+pub fn push_order_by_nulls(
+    writer: &mut dyn SqlWriter,
+    client: Client,
+    column: &str,
+    order: &str,
+    nulls: &NullsOrder,
+) {
+    if matches!(client, Client::Mysql) {
+        writer.push_sql(column);
+        writer.push_sql(match nulls {
+            NullsOrder::First => " IS NULL DESC, ",
+            NullsOrder::Last => " IS NULL ASC, ",
+        });
+    }
+    writer.push_sql(column);
+    writer.push_sql(" ");
+    writer.push_sql(order);
+    if !matches!(client, Client::Mysql) {
+        writer.push_sql(match nulls {
+            NullsOrder::First => " NULLS FIRST",
+            NullsOrder::Last => " NULLS LAST",
+        });
+    }
+}
@@ -1,45 +1,125 @@
 use crate::{
     builder::ChainBuilder,
-    types::{Method, Select},
+    common::{
+        expr_compiler::compile_expr,
+        list_compiler::push_columns,
+        placeholder::{write_named, write_raw, write_raw_binds},
+    },
+    types::{Client, Method, MissingColumn, Select},
+    writer::SqlWriter,
 };
 use serde_json::Value;
+use std::collections::BTreeSet;
 
 pub trait ToSqlProvider {
-    fn to_sql(&self, chain_builder: &ChainBuilder) -> (String, Vec<Value>);
+    fn write_sql(&self, chain_builder: &ChainBuilder, writer: &mut dyn SqlWriter);
+    fn client(&self) -> Client;
+    // `where_in` lists longer than this compile to one JSON bind
+    fn in_list_threshold(&self) -> Option<usize>;
 }
 
 pub fn method_compiler_with_provider<T: ToSqlProvider>(
     chain_builder: &ChainBuilder,
     to_sql_provider: &T,
-) -> (String, Vec<Value>) {
+    writer: &mut dyn SqlWriter,
+) {
     match chain_builder.method {
-        Method::Select => select_compiler(chain_builder, to_sql_provider),
-        Method::Insert => insert_into_compiler(chain_builder),
-        Method::InsertMany => insert_many_compiler(chain_builder),
-        Method::Update => update_compiler(chain_builder),
-        Method::Delete => delete_compiler(chain_builder),
+        Method::Select => select_compiler(chain_builder, to_sql_provider, writer),
+        Method::Insert => insert_into_compiler(chain_builder, to_sql_provider, writer),
+        Method::InsertMany => insert_many_compiler(chain_builder, to_sql_provider, writer),
+        Method::Update => update_compiler(chain_builder, to_sql_provider, writer),
+        Method::Delete => delete_compiler(chain_builder, to_sql_provider, writer),
+    }
+}
+
+// `table_raw`, or `db.table`
+fn table_compiler(chain_builder: &ChainBuilder, client: &Client, writer: &mut dyn SqlWriter) {
+    if let Some((table, binds)) = &chain_builder.table_raw {
+        write_raw_binds(writer, client, table, binds);
+    } else if let Some(table) = &chain_builder.table {
+        if let Some(db) = &chain_builder.db {
+            writer.push_sql(db);
+            writer.push_sql(".");
+        }
+        writer.push_sql(table);
+    }
+}
+
+// INSERT [IGNORE] INTO table
+fn insert_head_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    if !chain_builder.insert_ignore {
+        writer.push_sql("INSERT INTO ");
+    } else if matches!(to_sql_provider.client(), Client::Sqlite) {
+        writer.push_sql("INSERT OR IGNORE INTO ");
+    } else {
+        writer.push_sql("INSERT IGNORE INTO ");
+    }
+    table_compiler(chain_builder, &to_sql_provider.client(), writer);
+}
+
+// ON DUPLICATE KEY UPDATE (MySQL) / ON CONFLICT DO UPDATE SET (SQLite)
+fn upsert_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let data = match chain_builder.upsert.as_ref().and_then(|v| v.as_object()) {
+        Some(data) if !data.is_empty() => data,
+        _ => return,
+    };
+    if matches!(to_sql_provider.client(), Client::Sqlite) {
+        writer.push_sql(" ON CONFLICT DO UPDATE SET ");
+    } else {
+        writer.push_sql(" ON DUPLICATE KEY UPDATE ");
+    }
+    let mut keys = data.keys().collect::<Vec<&String>>();
+    keys.sort();
+    let mut is_first = true;
+    for key in keys {
+        if is_first {
+            is_first = false;
+        } else {
+            writer.push_sql(", ");
+        }
+        writer.push_sql(key.as_str());
+        writer.push_sql(" = ");
+        writer.push_bind(data[key.as_str()].clone());
     }
 }
 
 // Insert
-fn insert_into_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
-    let mut insert_sql = String::new();
-    let mut insert_binds: Vec<serde_json::Value> = vec![];
-    insert_sql.push_str("INSERT INTO ");
+fn insert_into_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    insert_head_compiler(chain_builder, to_sql_provider, writer);
 
-    if let Some((table, val)) = &chain_builder.table_raw {
-        insert_sql.push_str(table);
-        if let Some(val) = val {
-            insert_binds.extend(val.clone());
+    // INSERT INTO ... SELECT
+    if let Some((columns, source)) = &chain_builder.insert_select {
+        if !columns.is_empty() {
+            writer.push_sql(" (");
+            push_columns(writer, columns);
+            writer.push_sql(")");
         }
-    } else if let Some(table) = &chain_builder.table {
-        if let Some(db) = &chain_builder.db {
-            insert_sql.push_str(format!("{}.", db).as_str());
+        writer.push_sql(" ");
+        if chain_builder.upsert.is_some() && matches!(to_sql_provider.client(), Client::Sqlite) {
+            // SQLite needs a WHERE clause to tell the upsert apart from a join constraint
+            writer.push_sql("SELECT * FROM (");
+            to_sql_provider.write_sql(source, writer);
+            writer.push_sql(") WHERE true");
+        } else {
+            to_sql_provider.write_sql(source, writer);
         }
-        insert_sql.push_str(table.as_str());
+        upsert_compiler(chain_builder, to_sql_provider, writer);
+        return;
     }
 
-    insert_sql.push_str(" (");
+    writer.push_sql(" (");
     let mut is_first = true;
     let map_default = serde_json::Map::new();
     let data = chain_builder
@@ -53,22 +133,21 @@ fn insert_into_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
         if is_first {
             is_first = false;
         } else {
-            insert_sql.push_str(", ");
+            writer.push_sql(", ");
         }
-        insert_sql.push_str(key.as_str());
+        writer.push_sql(key.as_str());
     }
-    insert_sql.push_str(") VALUES (");
+    writer.push_sql(") VALUES (");
     is_first = true;
     for key in keys.iter().take(len) {
         if is_first {
             is_first = false;
         } else {
-            insert_sql.push_str(", ");
+            writer.push_sql(", ");
         }
-        insert_sql.push('?');
         match data.get(key.as_str()) {
             Some(value) => {
-                insert_binds.push(value.clone());
+                writer.push_bind(value.clone());
             }
             None => {
                 println!("[Err] key: {:?}", key);
@@ -78,169 +157,201 @@ fn insert_into_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
         }
     }
 
-    insert_sql.push(')');
-
-    (insert_sql, insert_binds)
+    writer.push_sql(")");
+    upsert_compiler(chain_builder, to_sql_provider, writer);
 }
 
 // InsertMany
-fn insert_many_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
-    let mut insert_sql = String::new();
-    let mut insert_binds: Vec<serde_json::Value> = vec![];
+fn insert_many_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let map_default = serde_json::Map::new();
+    let vec_default = vec![];
+    let rows = chain_builder
+        .insert_update
+        .as_array()
+        .unwrap_or(&vec_default)
+        .iter()
+        .map(|row| row.as_object().unwrap_or(&map_default))
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        panic!("[Err] insert_many: no rows to insert");
+    }
 
-    insert_sql.push_str("INSERT INTO ");
-    if let Some((table, val)) = &chain_builder.table_raw {
-        insert_sql.push_str(table);
-        if let Some(val) = val {
-            insert_binds.extend(val.clone());
+    // SQLite has no DEFAULT in VALUES: consecutive rows with the same keys get their
+    // own INSERT that leaves the missing columns out
+    if matches!(chain_builder.missing_column, MissingColumn::Default)
+        && matches!(to_sql_provider.client(), Client::Sqlite)
+    {
+        let mut is_first = true;
+        for run in rows.chunk_by(|a, b| a.keys().eq(b.keys())) {
+            if is_first {
+                is_first = false;
+            } else {
+                writer.push_sql("; ");
+            }
+            let keys = run[0].keys().collect::<BTreeSet<&String>>();
+            insert_rows_compiler(chain_builder, to_sql_provider, &keys, run, writer);
         }
-    } else if let Some(table) = &chain_builder.table {
-        if let Some(db) = &chain_builder.db {
-            insert_sql.push_str(format!("{}.", db).as_str());
+        return;
+    }
+
+    // columns are the union of every row's keys, sorted
+    let keys = rows
+        .iter()
+        .flat_map(|row| row.keys())
+        .collect::<BTreeSet<&String>>();
+    insert_rows_compiler(chain_builder, to_sql_provider, &keys, &rows, writer);
+}
+
+// `INSERT INTO table (keys) VALUES (...), (...)` plus the upsert clause
+fn insert_rows_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    keys: &BTreeSet<&String>,
+    rows: &[&serde_json::Map<String, Value>],
+    writer: &mut dyn SqlWriter,
+) {
+    // SQLite rejects `() VALUES ()`: rows without columns become `DEFAULT VALUES`, one
+    // statement per row (MySQL accepts the empty lists as written)
+    if keys.is_empty() && matches!(to_sql_provider.client(), Client::Sqlite) {
+        if chain_builder.upsert.is_some() {
+            panic!("[Err] insert_many_compiler: SQLite cannot upsert rows with no columns");
         }
-        insert_sql.push_str(table.as_str());
+        let mut is_first = true;
+        for _ in rows {
+            if is_first {
+                is_first = false;
+            } else {
+                writer.push_sql("; ");
+            }
+            insert_head_compiler(chain_builder, to_sql_provider, writer);
+            writer.push_sql(" DEFAULT VALUES");
+        }
+        return;
     }
 
-    insert_sql.push_str(" (");
+    insert_head_compiler(chain_builder, to_sql_provider, writer);
+
+    writer.push_sql(" (");
     let mut is_first = true;
-    let map_default = serde_json::Map::new();
-    let vec_default = vec![];
-    let data = chain_builder
-        .insert_update
-        .as_array()
-        .unwrap_or(&vec_default);
-    let mut keys = data[0]
-        .as_object()
-        .unwrap_or(&map_default)
-        .keys()
-        .collect::<Vec<&String>>();
-    keys.sort();
-    let len = keys.len();
-    for key in keys.iter().take(len) {
+    for key in keys.iter() {
         if is_first {
             is_first = false;
         } else {
-            insert_sql.push_str(", ");
+            writer.push_sql(", ");
         }
-        insert_sql.push_str(key.as_str());
+        writer.push_sql(key.as_str());
     }
-    insert_sql.push_str(") VALUES ");
+    writer.push_sql(") VALUES ");
     is_first = true;
-    for row in data.iter() {
+    for row in rows.iter() {
         if is_first {
             is_first = false;
         } else {
-            insert_sql.push_str(", ");
+            writer.push_sql(", ");
         }
-        insert_sql.push('(');
+        writer.push_sql("(");
         let mut is_first = true;
-        let row = row.as_object().unwrap_or(&map_default);
-        for key in keys.iter().take(len) {
+        for key in keys.iter() {
             if is_first {
                 is_first = false;
             } else {
-                insert_sql.push_str(", ");
+                writer.push_sql(", ");
             }
-            insert_sql.push('?');
             match row.get(key.as_str()) {
                 Some(value) => {
-                    insert_binds.push(value.clone());
-                }
-                None => {
-                    println!("[Err] key: {:?}", key);
-                    println!("[Err] row: {:?}", row);
-                    panic!("[Err] insert_many_compiler: row.get(key.as_str()) is None");
+                    writer.push_bind(value.clone());
                 }
+                None => match chain_builder.missing_column {
+                    MissingColumn::Null => {
+                        writer.push_bind(Value::Null);
+                    }
+                    MissingColumn::Default => {
+                        writer.push_sql("DEFAULT");
+                    }
+                    MissingColumn::Error => {
+                        panic!(
+                            "[Err] insert_many_compiler: row {:?} has no value for column {:?}",
+                            row, key
+                        );
+                    }
+                },
             }
         }
-        insert_sql.push(')');
+        writer.push_sql(")");
     }
-
-    (insert_sql, insert_binds)
+    upsert_compiler(chain_builder, to_sql_provider, writer);
 }
 
 // Select with provider
 fn select_compiler<T: ToSqlProvider>(
     chain_builder: &ChainBuilder,
     to_sql_provider: &T,
-) -> (String, Vec<Value>) {
-    let mut select_sql = String::new();
-    let mut select_binds: Vec<serde_json::Value> = vec![];
+    writer: &mut dyn SqlWriter,
+) {
     if chain_builder.is_distinct {
-        select_sql.push_str("SELECT DISTINCT ");
+        writer.push_sql("SELECT DISTINCT ");
     } else {
-        select_sql.push_str("SELECT ");
+        writer.push_sql("SELECT ");
     }
 
     if chain_builder.select.is_empty() {
-        select_sql.push('*');
+        writer.push_sql("*");
     } else {
         let mut is_first = true;
         for select in &chain_builder.select {
             if is_first {
                 is_first = false;
             } else {
-                select_sql.push_str(", ");
+                writer.push_sql(", ");
             }
             match select {
                 Select::Columns(columns) => {
-                    select_sql.push_str(&columns.join(", "));
+                    push_columns(writer, columns);
                 }
                 Select::Raw(sql, binds) => {
-                    select_sql.push_str(sql.as_str());
-                    if let Some(binds) = binds {
-                        select_binds.extend(binds.clone());
-                    }
+                    write_raw(writer, &to_sql_provider.client(), sql, binds.as_deref());
+                }
+                Select::RawNamed(sql, values) => {
+                    write_named(writer, &to_sql_provider.client(), sql, values);
                 }
                 Select::Builder(as_name, c2) => {
-                    let (sub_sql, sub_binds) = to_sql_provider.to_sql(c2);
-                    select_sql.push_str("(");
-                    select_sql.push_str(&sub_sql);
-                    select_sql.push_str(") AS ");
-                    select_sql.push_str(as_name.as_str());
-                    select_binds.extend(sub_binds);
+                    writer.push_sql("(");
+                    to_sql_provider.write_sql(c2, writer);
+                    writer.push_sql(") AS ");
+                    writer.push_sql(as_name.as_str());
+                }
+                Select::Expr(expr, alias) => {
+                    compile_expr(expr, to_sql_provider, writer);
+                    if let Some(alias) = alias {
+                        writer.push_sql(" AS ");
+                        writer.push_sql(alias);
+                    }
                 }
             }
         }
     }
 
-    select_sql.push_str(" FROM ");
-    if let Some((table, val)) = &chain_builder.table_raw {
-        select_sql.push_str(table);
-        if let Some(val) = val {
-            select_binds.extend(val.clone());
-        }
-    } else if let Some(table) = &chain_builder.table {
-        if let Some(db) = &chain_builder.db {
-            select_sql.push_str(format!("{}.", db).as_str());
-        }
-        select_sql.push_str(table.as_str());
-    }
+    writer.push_sql(" FROM ");
+    table_compiler(chain_builder, &to_sql_provider.client(), writer);
     if let Some(as_name) = &chain_builder.as_name {
-        select_sql.push_str(" AS ");
-        select_sql.push_str(as_name);
+        writer.push_sql(" AS ");
+        writer.push_sql(as_name);
     }
-    (select_sql, select_binds)
 }
 
 // Update
-fn update_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
-    let mut update_sql = String::new();
-    let mut update_binds: Vec<serde_json::Value> = vec![];
-
-    update_sql.push_str("UPDATE ");
-    if let Some((table, val)) = &chain_builder.table_raw {
-        update_sql.push_str(table);
-        if let Some(val) = val {
-            update_binds.extend(val.clone());
-        }
-    } else if let Some(table) = &chain_builder.table {
-        if let Some(db) = &chain_builder.db {
-            update_sql.push_str(format!("{}.", db).as_str());
-        }
-        update_sql.push_str(table.as_str());
-    }
-    update_sql.push_str(" SET ");
+fn update_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    writer.push_sql("UPDATE ");
+    table_compiler(chain_builder, &to_sql_provider.client(), writer);
+    writer.push_sql(" SET ");
     let map_default = serde_json::Map::new();
     let data = chain_builder
         .insert_update
@@ -254,13 +365,13 @@ fn update_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
         if is_first {
             is_first = false;
         } else {
-            update_sql.push_str(", ");
+            writer.push_sql(", ");
         }
-        update_sql.push_str(key.as_str());
-        update_sql.push_str(" = ?");
+        writer.push_sql(key.as_str());
+        writer.push_sql(" = ");
         match data.get(key.as_str()) {
             Some(value) => {
-                update_binds.push(value.clone());
+                writer.push_bind(value.clone());
             }
             None => {
                 println!("[Err] key: {:?}", key);
@@ -269,26 +380,22 @@ fn update_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
             }
         }
     }
-
-    (update_sql, update_binds)
-}
-
-// Delete
-fn delete_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
-    let mut delete_sql = String::new();
-    let mut delete_binds: Vec<serde_json::Value> = vec![];
-    delete_sql.push_str("DELETE FROM ");
-    if let Some((table, val)) = &chain_builder.table_raw {
-        delete_sql.push_str(table);
-        if let Some(val) = val {
-            delete_binds.extend(val.clone());
-        }
-    } else if let Some(table) = &chain_builder.table {
-        if let Some(db) = &chain_builder.db {
-            delete_sql.push_str(format!("{}.", db).as_str());
+    for expr in chain_builder.set_expr.iter() {
+        if is_first {
+            is_first = false;
+        } else {
+            writer.push_sql(", ");
         }
-        delete_sql.push_str(table.as_str());
+        compile_expr(expr, to_sql_provider, writer);
     }
+}
 
-    (delete_sql, delete_binds)
+// Delete
+fn delete_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    writer.push_sql("DELETE FROM ");
+    table_compiler(chain_builder, &to_sql_provider.client(), writer);
 }
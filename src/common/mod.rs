@@ -1,4 +1,8 @@
+pub mod clause_compiler;
+pub mod expr_compiler;
 pub mod join_compiler;
+pub mod list_compiler;
 pub mod method_compiler;
 pub mod operator_to_sql;
+pub mod placeholder;
 pub mod statement_compiler;
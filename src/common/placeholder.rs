@@ -0,0 +1,174 @@
+use crate::types::{Client, RawBinds};
+use crate::writer::SqlWriter;
+use serde_json::{Map, Value};
+
+/// A piece of SQL split around its bind placeholders
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
+    /// Plain SQL text, copied as-is
+    Sql(&'a str),
+    /// A positional `?` placeholder
+    Positional,
+    /// A named `:name` placeholder (name without the colon)
+    Named(&'a str),
+}
+
+// Split SQL into text and placeholders, skipping quoted strings,
+// quoted identifiers and comments. MySQL treats a backslash inside a
+// string as an escape character, SQLite does not.
+pub fn tokenize(sql: &str, backslash_escapes: bool) -> Vec<Token<'_>> {
+    let bytes = sql.as_bytes();
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() {
+                    if backslash_escapes && bytes[i] == b'\\' && quote != b'`' {
+                        i += 2;
+                        continue;
+                    }
+                    if bytes[i] == quote {
+                        // doubled quote is an escaped quote
+                        if bytes.get(i + 1) == Some(&quote) {
+                            i += 2;
+                            continue;
+                        }
+                        break;
+                    }
+                    i += 1;
+                }
+                i += 1;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            // `:name`, but not `::type` casts or `:=` assignments
+            b':' if (i == 0 || bytes[i - 1] != b':')
+                && bytes
+                    .get(i + 1)
+                    .is_some_and(|b| b.is_ascii_alphabetic() || *b == b'_') =>
+            {
+                if start < i {
+                    tokens.push(Token::Sql(&sql[start..i]));
+                }
+                let name_start = i + 1;
+                i = name_start;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token::Named(&sql[name_start..i]));
+                start = i;
+            }
+            b'?' => {
+                if start < i {
+                    tokens.push(Token::Sql(&sql[start..i]));
+                }
+                tokens.push(Token::Positional);
+                i += 1;
+                start = i;
+            }
+            _ => {
+                i += 1;
+            }
+        }
+    }
+    if start < sql.len() {
+        tokens.push(Token::Sql(&sql[start..]));
+    }
+    tokens
+}
+
+// `*_raw_named` values by name, checked against the placeholders when compiled
+pub fn named_binds<'a>(binds: impl IntoIterator<Item = (&'a str, Value)>) -> Map<String, Value> {
+    binds
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+// Write a raw fragment with `?` placeholders.
+// Panics if the placeholders do not match the binds, whatever the writer;
+// SQLite `?NNN` placeholders may repeat an index, so they are not counted.
+pub fn write_raw(writer: &mut dyn SqlWriter, client: &Client, sql: &str, binds: Option<&[Value]>) {
+    let binds = binds.unwrap_or_default();
+    let tokens = tokenize(sql, matches!(client, Client::Mysql));
+    let numbered = tokens.windows(2).any(|pair| {
+        matches!(pair, [Token::Positional, Token::Sql(sql)] if sql.starts_with(|c: char| c.is_ascii_digit()))
+    });
+    let placeholders = tokens
+        .iter()
+        .filter(|token| **token == Token::Positional)
+        .count();
+    if !numbered && placeholders != binds.len() {
+        panic!(
+            "[Err] raw: {} placeholders for {} binds in {:?}",
+            placeholders,
+            binds.len(),
+            sql
+        );
+    }
+    writer.push_raw(sql, binds)
+}
+
+// Write a raw fragment, resolving its `:name` placeholders for `client`
+pub fn write_named(
+    writer: &mut dyn SqlWriter,
+    client: &Client,
+    sql: &str,
+    values: &Map<String, Value>,
+) {
+    let (sql, binds) = resolve_named(sql, values, client);
+    writer.push_raw(&sql, &binds);
+}
+
+// Write a raw table, join or trailing fragment
+pub fn write_raw_binds(writer: &mut dyn SqlWriter, client: &Client, sql: &str, binds: &RawBinds) {
+    match binds {
+        RawBinds::Positional(binds) => write_raw(writer, client, sql, binds.as_deref()),
+        RawBinds::Named(values) => write_named(writer, client, sql, values),
+    }
+}
+
+// Rewrite `:name` placeholders to `?` and collect their values in order.
+// A name may appear several times; every name must have a value and every
+// value must be used.
+fn resolve_named(sql: &str, values: &Map<String, Value>, client: &Client) -> (String, Vec<Value>) {
+    let mut used = vec![];
+    let mut resolved_sql = String::with_capacity(sql.len());
+    let mut resolved_binds = vec![];
+    for token in tokenize(sql, matches!(client, Client::Mysql)) {
+        match token {
+            Token::Sql(sql) => resolved_sql.push_str(sql),
+            Token::Positional => {
+                panic!(
+                    "[Err] named binds: positional ? mixed with :name placeholders in {:?}",
+                    sql
+                )
+            }
+            Token::Named(name) => match values.get(name) {
+                Some(value) => {
+                    resolved_sql.push('?');
+                    resolved_binds.push(value.clone());
+                    used.push(name);
+                }
+                None => panic!("[Err] named binds: no value for :{}", name),
+            },
+        }
+    }
+    if let Some(unused) = values.keys().find(|name| !used.contains(&name.as_str())) {
+        panic!("[Err] named binds: :{} is not used in {:?}", unused, sql);
+    }
+    (resolved_sql, resolved_binds)
+}
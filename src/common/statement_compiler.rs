@@ -1,115 +1,252 @@
-use crate::{builder::ChainBuilder, query::Operator, types::Statement};
+use super::{
+    expr_compiler::{compile_expr, is_condition_list},
+    method_compiler::ToSqlProvider,
+    placeholder::{write_named, write_raw},
+};
+use crate::{
+    builder::ChainBuilder,
+    expr::{BinaryOp, Expr},
+    query::Operator,
+    template::param_name,
+    types::{Client, Statement},
+    writer::SqlWriter,
+};
 use serde_json::Value;
 
-pub fn statement_compiler(chain_builder: &ChainBuilder) -> (String, Vec<Value>) {
-    let mut statement_sql = String::new();
-    let mut statement_binds: Vec<serde_json::Value> = vec![];
+// ` WHERE ...`, or nothing when every statement is an empty group
+pub fn statement_compiler<T: ToSqlProvider>(
+    chain_builder: &ChainBuilder,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    let statements = &chain_builder.query.statement;
+    if statements.iter().all(is_empty_group) {
+        return;
+    }
+    writer.push_sql(" WHERE ");
+    compile_statements(statements, " AND ", to_sql_provider, writer);
+}
+
+// Groups left empty (e.g. by `when(false, ..)`) are skipped
+fn is_empty_group(statement: &Statement) -> bool {
+    match statement {
+        Statement::OrChain(qb)
+        | Statement::SubChain(qb)
+        | Statement::OrGroup(qb)
+        | Statement::NotGroup(qb) => qb.statement.is_empty(),
+        _ => false,
+    }
+}
+
+// Groups that already wrap themselves in parentheses
+fn is_group(statement: &Statement) -> bool {
+    match statement {
+        Statement::SubChain(qb) | Statement::OrGroup(qb) => !qb.statement.is_empty(),
+        _ => false,
+    }
+}
+
+// Write sibling statements joined by `joiner` (" AND " or " OR "),
+// nested groups write into the same writer
+pub(crate) fn compile_statements<T: ToSqlProvider>(
+    statements: &[Statement],
+    joiner: &str,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
     let mut is_first = true;
-    let mut build_statement = |statement: &Statement| match statement {
-        Statement::Value(field, operator, value) => {
-            if (*operator == Operator::In || *operator == Operator::NotIn)
-                && matches!(value, Value::Array(arr) if arr.is_empty())
-            {
-                if is_first {
-                    is_first = false;
+    for statement in statements.iter() {
+        match statement {
+            statement if is_empty_group(statement) => {}
+            Statement::OrChain(qb) => {
+                push_joiner(writer, &mut is_first, " OR ");
+                if qb.statement.len() > 1 {
+                    writer.push_sql("(");
+                    compile_statements(&qb.statement, " AND ", to_sql_provider, writer);
+                    writer.push_sql(")");
                 } else {
-                    statement_sql.push_str(" AND ");
+                    compile_statements(&qb.statement, " AND ", to_sql_provider, writer);
                 }
-                statement_sql.push_str(if *operator == Operator::In {
-                    "1 = 0"
+            }
+            Statement::SubChain(qb) => {
+                push_joiner(writer, &mut is_first, joiner);
+                writer.push_sql("(");
+                compile_statements(&qb.statement, " AND ", to_sql_provider, writer);
+                writer.push_sql(")");
+            }
+            Statement::OrGroup(qb) => {
+                push_joiner(writer, &mut is_first, joiner);
+                writer.push_sql("(");
+                compile_statements(&qb.statement, " OR ", to_sql_provider, writer);
+                writer.push_sql(")");
+            }
+            Statement::NotGroup(qb) => {
+                push_joiner(writer, &mut is_first, joiner);
+                // NOT (a OR b) rather than NOT ((a OR b))
+                if qb.statement.len() == 1 && is_group(&qb.statement[0]) {
+                    writer.push_sql("NOT ");
+                    compile_statements(&qb.statement, " AND ", to_sql_provider, writer);
                 } else {
-                    "1 = 1"
-                });
-                return;
+                    writer.push_sql("NOT (");
+                    compile_statements(&qb.statement, " AND ", to_sql_provider, writer);
+                    writer.push_sql(")");
+                }
             }
-
-            let (operator_str, is_bind) = super::operator_to_sql::operator_to_sql(operator);
-            if is_first {
-                is_first = false;
-            } else {
-                statement_sql.push_str(" AND ");
+            Statement::Value(field, operator, value) => {
+                push_joiner(writer, &mut is_first, joiner);
+                compile_value(field, operator, value, to_sql_provider, writer);
             }
-            statement_sql.push_str(field);
-            statement_sql.push(' ');
-            if *operator == Operator::Between || *operator == Operator::NotBetween {
-                statement_sql.push_str(&format!("{} ? AND ?", operator_str));
-                if let Some(array) = value.as_array() {
-                    for v in array {
-                        statement_binds.push(v.clone());
-                    }
+            Statement::Exists(is_not, sub_builder) => {
+                push_joiner(writer, &mut is_first, joiner);
+                if *is_not {
+                    writer.push_sql("NOT ");
                 }
-            } else {
-                statement_sql.push_str(operator_str);
-                if is_bind {
-                    if *operator == Operator::In || *operator == Operator::NotIn {
-                        if let Value::Array(value) = value {
-                            statement_sql.push_str(" (");
-                            let mut first = true;
-                            for v in value {
-                                if first {
-                                    first = false;
-                                } else {
-                                    statement_sql.push(',');
-                                }
-                                statement_sql.push('?');
-                                statement_binds.push(v.clone());
-                            }
-                            statement_sql.push(')');
-                        } else {
-                            statement_sql.push_str(" ?");
-                            statement_binds.push(value.clone());
-                        }
-                    } else {
-                        statement_sql.push_str(" ?");
-                        statement_binds.push(value.clone());
-                    }
+                writer.push_sql("EXISTS (");
+                to_sql_provider.write_sql(sub_builder, writer);
+                writer.push_sql(")");
+            }
+            Statement::Expr(expr) => {
+                push_joiner(writer, &mut is_first, joiner);
+                // keep `a OR b` from leaking into the surrounding AND list
+                if matches!(expr, Expr::Binary(_, BinaryOp::Or, _)) || is_condition_list(expr) {
+                    writer.push_sql("(");
+                    compile_expr(expr, to_sql_provider, writer);
+                    writer.push_sql(")");
+                } else {
+                    compile_expr(expr, to_sql_provider, writer);
                 }
             }
-        }
-        Statement::OrChain(qb) => {
-            if is_first {
-                is_first = false;
-            } else {
-                statement_sql.push_str(" OR ");
+            Statement::Raw((raw, raw_binds)) => {
+                push_joiner(writer, &mut is_first, joiner);
+                write_raw(writer, &to_sql_provider.client(), raw, raw_binds.as_deref());
             }
-            let mut c = chain_builder.clone();
-            c.query = *qb.clone();
-            let (sql, binds) = statement_compiler(&c);
-            if qb.statement.len() > 1 {
-                statement_sql.push_str(&format!("({})", sql));
-            } else {
-                statement_sql.push_str(&sql);
+            Statement::RawNamed(raw, values) => {
+                push_joiner(writer, &mut is_first, joiner);
+                write_named(writer, &to_sql_provider.client(), raw, values);
             }
-            statement_binds.extend(binds);
         }
-        Statement::SubChain(qb) => {
-            if is_first {
-                is_first = false;
-            } else {
-                statement_sql.push_str(" AND ");
-            }
-            let mut c = chain_builder.clone();
-            c.query = *qb.clone();
-            let (sql, binds) = statement_compiler(&c);
-            statement_sql.push_str(&format!("({})", sql));
-            statement_binds.extend(binds);
+    }
+}
+
+fn push_joiner(writer: &mut dyn SqlWriter, is_first: &mut bool, joiner: &str) {
+    if *is_first {
+        *is_first = false;
+    } else {
+        writer.push_sql(joiner);
+    }
+}
+
+fn compile_value<T: ToSqlProvider>(
+    field: &str,
+    operator: &Operator,
+    value: &Value,
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) {
+    if (*operator == Operator::In || *operator == Operator::NotIn)
+        && matches!(value, Value::Array(arr) if arr.is_empty())
+    {
+        writer.push_sql(if *operator == Operator::In {
+            "1 = 0"
+        } else {
+            "1 = 1"
+        });
+        return;
+    }
+    if let (Operator::In | Operator::NotIn, Value::Array(values)) = (operator, value) {
+        if compile_json_in_list(field, operator, values, to_sql_provider, writer) {
+            return;
         }
-        Statement::Raw((sql, binds)) => {
-            if is_first {
-                is_first = false;
-            } else {
-                statement_sql.push_str(" AND ");
+    }
+
+    let (operator_str, is_bind) = super::operator_to_sql::operator_to_sql(operator);
+    writer.push_sql(field);
+    writer.push_sql(" ");
+    writer.push_sql(operator_str);
+    if *operator == Operator::Between || *operator == Operator::NotBetween {
+        let mut bounds = value.as_array().into_iter().flatten();
+        writer.push_sql(" ");
+        writer.push_bind(bounds.next().cloned().unwrap_or(Value::Null));
+        writer.push_sql(" AND ");
+        writer.push_bind(bounds.next().cloned().unwrap_or(Value::Null));
+    } else if is_bind {
+        match (operator, value) {
+            (Operator::In | Operator::NotIn, Value::Array(values)) => {
+                writer.push_sql(" (");
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        writer.push_sql(",");
+                    }
+                    writer.push_bind(value.clone());
+                }
+                writer.push_sql(")");
             }
-            statement_sql.push_str(sql);
-            if let Some(binds) = binds {
-                statement_binds.extend(binds.clone());
+            _ => {
+                writer.push_sql(" ");
+                writer.push_bind(value.clone());
             }
         }
+    }
+}
+
+// `field [NOT] IN (SELECT value FROM ...)` reading the list from one JSON array bind,
+// once the list is longer than the provider's threshold; false when the list stays inline
+fn compile_json_in_list<T: ToSqlProvider>(
+    field: &str,
+    operator: &Operator,
+    values: &[Value],
+    to_sql_provider: &T,
+    writer: &mut dyn SqlWriter,
+) -> bool {
+    match to_sql_provider.in_list_threshold() {
+        Some(threshold) if values.len() > threshold => {}
+        _ => return false,
+    }
+    // template slots are filled per bind, so they need their own placeholders
+    if values.iter().any(|value| param_name(value).is_some()) {
+        return false;
+    }
+    // JSON_TABLE needs a column type, only integer lists have one
+    let column_type = match to_sql_provider.client() {
+        Client::Mysql => match json_table_column_type(values) {
+            Some(column_type) => Some(column_type),
+            None => return false,
+        },
+        _ => None,
     };
 
-    for statement in chain_builder.query.statement.iter() {
-        build_statement(statement);
+    writer.push_sql(field);
+    writer.push_sql(if *operator == Operator::In {
+        " IN (SELECT value FROM "
+    } else {
+        " NOT IN (SELECT value FROM "
+    });
+    let list = Value::String(Value::Array(values.to_vec()).to_string());
+    match column_type {
+        Some(column_type) => {
+            writer.push_sql("JSON_TABLE(");
+            writer.push_bind(list);
+            writer.push_sql(", '$[*]' COLUMNS (value ");
+            writer.push_sql(column_type);
+            writer.push_sql(" PATH '$')) AS in_list)");
+        }
+        None => {
+            writer.push_sql("json_each(");
+            writer.push_bind(list);
+            writer.push_sql("))");
+        }
     }
+    true
+}
 
-    (statement_sql, statement_binds)
+// BIGINT [UNSIGNED] for integer lists. Strings stay inline: a JSON_TABLE
+// column has the utf8mb4_bin collation, which clashes with the compared column's.
+fn json_table_column_type(values: &[Value]) -> Option<&'static str> {
+    if values.iter().all(|v| v.is_i64()) {
+        Some("BIGINT")
+    } else if values.iter().all(|v| v.is_u64()) {
+        Some("BIGINT UNSIGNED")
+    } else {
+        None
+    }
 }
@@ -0,0 +1,33 @@
+//! Owned compiled queries, independent of the builder that produced them
+
+use crate::types::Client;
+use serde_json::Value;
+
+/// SQL and binds produced by `ChainBuilder::build`
+///
+/// Owns its data (`Clone + Send + 'static`), so the builder can be dropped
+/// and the query handed to another task. With the sqlx features enabled it
+/// can be executed directly (`execute`, `fetch_all`, `fetch_one`,
+/// `fetch_optional`, or `to_sqlx_query` for anything else).
+///
+/// ```rust
+/// use chain_builder::{ChainBuilder, Client, WhereClauses};
+/// use serde_json::Value;
+///
+/// let query = {
+///     let mut builder = ChainBuilder::new(Client::Mysql);
+///     builder.table("users").query(|qb| qb.where_eq("id", Value::from(1)));
+///     builder.build()
+/// };
+/// let handle = std::thread::spawn(move || query.sql);
+/// assert_eq!(handle.join().unwrap(), "SELECT * FROM users WHERE id = ?");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    /// Compiled SQL with positional placeholders
+    pub sql: String,
+    /// Bind values in placeholder order
+    pub binds: Vec<Value>,
+    /// Client the SQL was compiled for
+    pub dialect: Client,
+}
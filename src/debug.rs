@@ -0,0 +1,215 @@
+//! Debug rendering of queries with bind values inlined as SQL literals
+
+use crate::builder::ChainBuilder;
+use crate::common::placeholder::{tokenize, Token};
+use crate::expr::Expr;
+use crate::query::join::JoinStatement;
+use crate::types::{Client, Statement};
+use crate::visitor::{
+    walk_chain_builder_mut, walk_expr_mut, walk_join_statement_mut, walk_statement_mut, VisitorMut,
+};
+use serde_json::Value;
+
+/// Placeholder shown in place of redacted values
+const REDACTED: &str = "***";
+
+type RedactPredicate = Box<dyn Fn(Option<&str>, &Value) -> bool + Send + Sync>;
+
+/// Policy deciding which bind values are hidden in debug SQL
+///
+/// Columns are matched case-insensitively on their last path segment, so
+/// `"password"` also hides `users.password`. Predicates receive the column
+/// name when it is known and `None` for values bound by raw fragments.
+#[derive(Default)]
+pub struct Redaction {
+    columns: Vec<String>,
+    predicates: Vec<RedactPredicate>,
+}
+
+impl Redaction {
+    /// Create an empty redaction policy
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hide every value bound to this column
+    pub fn column(mut self, column: &str) -> Self {
+        self.columns.push(normalize_column(column));
+        self
+    }
+
+    /// Hide every value the predicate returns true for
+    pub fn predicate(
+        mut self,
+        predicate: impl Fn(Option<&str>, &Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    fn matches(&self, column: Option<&str>, value: &Value) -> bool {
+        if let Some(column) = column {
+            if self.columns.contains(&normalize_column(column)) {
+                return true;
+            }
+        }
+        self.predicates.iter().any(|p| p(column, value))
+    }
+}
+
+impl std::fmt::Debug for Redaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Redaction")
+            .field("columns", &self.columns)
+            .field("predicates", &self.predicates.len())
+            .finish()
+    }
+}
+
+// `db`.`users`.`Password` -> password
+fn normalize_column(column: &str) -> String {
+    column
+        .rsplit('.')
+        .next()
+        .unwrap_or(column)
+        .trim_matches(|c| c == '`' || c == '"')
+        .to_lowercase()
+}
+
+impl ChainBuilder {
+    /// Render the query with every bind value inlined, for logging only
+    ///
+    /// The output is meant for humans: never execute it, use `to_sql()`.
+    pub fn to_debug_sql(&self) -> String {
+        self.to_debug_sql_with(&Redaction::new())
+    }
+
+    /// Render the query with bind values inlined and redacted by `redaction`
+    pub fn to_debug_sql_with(&self, redaction: &Redaction) -> String {
+        let mut chain_builder = self.clone();
+        Redactor(redaction).visit_chain_builder_mut(&mut chain_builder);
+        let (sql, binds) = chain_builder.to_sql();
+        let backslash_escapes = matches!(self.client, Client::Mysql);
+        let mut binds = binds.into_iter();
+        let mut debug_sql = String::with_capacity(sql.len());
+        for token in tokenize(&sql, backslash_escapes) {
+            match token {
+                Token::Sql(sql) => debug_sql.push_str(sql),
+                // named fragments are rewritten to `?` by `to_sql`, so any `:name` left is not ours
+                Token::Named(name) => {
+                    debug_sql.push(':');
+                    debug_sql.push_str(name);
+                }
+                Token::Positional => match binds.next() {
+                    Some(value) if redaction.matches(None, &value) => {
+                        debug_sql.push_str(&to_literal(&self.client, &redacted()));
+                    }
+                    Some(value) => debug_sql.push_str(&to_literal(&self.client, &value)),
+                    None => debug_sql.push('?'),
+                },
+            }
+        }
+        debug_sql
+    }
+}
+
+fn redacted() -> Value {
+    Value::String(REDACTED.to_string())
+}
+
+// Render a JSON value as a SQL literal for the given client
+fn to_literal(client: &Client, value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => match client {
+            Client::Sqlite => if *b { "1" } else { "0" }.to_string(),
+            _ => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        },
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => quote_string(client, s),
+        Value::Array(_) | Value::Object(_) => quote_string(client, &value.to_string()),
+    }
+}
+
+// Quote a string as a SQL literal, escaping backslashes on MySQL
+pub(crate) fn quote_string(client: &Client, s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => quoted.push_str("''"),
+            '\\' if matches!(client, Client::Mysql) => quoted.push_str("\\\\"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+// Replace values tied to a known column before compiling
+struct Redactor<'a>(&'a Redaction);
+
+impl VisitorMut for Redactor<'_> {
+    fn visit_chain_builder_mut(&mut self, chain_builder: &mut ChainBuilder) {
+        redact_data(&mut chain_builder.insert_update, self.0);
+        if let Some(upsert) = &mut chain_builder.upsert {
+            redact_data(upsert, self.0);
+        }
+        walk_chain_builder_mut(self, chain_builder);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        if let Statement::Value(column, _, value) = statement {
+            if let Value::Array(values) = value {
+                for v in values.iter_mut() {
+                    if self.0.matches(Some(column), v) {
+                        *v = redacted();
+                    }
+                }
+            } else if self.0.matches(Some(column), value) {
+                *value = redacted();
+            }
+        }
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        // `column <op> value` carries the column name the redaction rules match on
+        if let Expr::Binary(lhs, _, rhs) = expr {
+            if let (Expr::Column(column), Expr::Value(value)) = (&**lhs, &mut **rhs) {
+                if self.0.matches(Some(column), value) {
+                    *value = redacted();
+                }
+            }
+        }
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_join_statement_mut(&mut self, statement: &mut JoinStatement) {
+        if let JoinStatement::OnVal(column, _, value) = statement {
+            if self.0.matches(Some(column), value) {
+                *value = redacted();
+            }
+        }
+        walk_join_statement_mut(self, statement);
+    }
+}
+
+// INSERT/UPDATE payloads: an object, or an array of objects for insert_many
+fn redact_data(data: &mut Value, redaction: &Redaction) {
+    match data {
+        Value::Object(map) => {
+            for (column, value) in map.iter_mut() {
+                if redaction.matches(Some(column), value) {
+                    *value = redacted();
+                }
+            }
+        }
+        Value::Array(rows) => {
+            for row in rows.iter_mut() {
+                redact_data(row, redaction);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,181 @@
+//! Structured query plans parsed from EXPLAIN output
+
+use serde_json::Value;
+
+/// One table access in a query plan
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PlanNode {
+    /// Table name or alias being read
+    pub table: Option<String>,
+    /// Access method as reported by the database (`ALL`, `ref`, `Index lookup`, `SCAN`, `SEARCH`, ...)
+    pub access_type: String,
+    /// Index used for the access, if any
+    pub index: Option<String>,
+    /// Estimated number of rows read
+    pub rows: Option<f64>,
+    /// Actual number of rows read (EXPLAIN ANALYZE only)
+    pub actual_rows: Option<f64>,
+    /// Original plan line or JSON fragment this node was parsed from
+    pub detail: String,
+}
+
+impl PlanNode {
+    /// Whether this access reads the whole table without an index
+    pub fn is_full_scan(&self) -> bool {
+        self.index.is_none() && matches!(self.access_type.as_str(), "ALL" | "Table scan" | "SCAN")
+    }
+}
+
+/// Parsed EXPLAIN output
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct QueryPlan {
+    /// Table accesses in the order they appear in the plan
+    pub nodes: Vec<PlanNode>,
+}
+
+impl QueryPlan {
+    /// Parse MySQL `EXPLAIN FORMAT=JSON` output
+    pub fn from_mysql_json(plan: &Value) -> QueryPlan {
+        let mut nodes = vec![];
+        collect_mysql_json(plan, &mut nodes);
+        QueryPlan { nodes }
+    }
+
+    /// Parse MySQL `EXPLAIN ANALYZE` (or `FORMAT=TREE`) output
+    pub fn from_mysql_tree(plan: &str) -> QueryPlan {
+        let nodes = plan.lines().filter_map(parse_mysql_tree_line).collect();
+        QueryPlan { nodes }
+    }
+
+    /// Parse the `detail` column of SQLite `EXPLAIN QUERY PLAN` rows
+    pub fn from_sqlite<'a>(details: impl IntoIterator<Item = &'a str>) -> QueryPlan {
+        let nodes = details
+            .into_iter()
+            .filter_map(parse_sqlite_detail)
+            .collect();
+        QueryPlan { nodes }
+    }
+
+    /// Find the first access to a table
+    pub fn node(&self, table: &str) -> Option<&PlanNode> {
+        self.nodes
+            .iter()
+            .find(|node| node.table.as_deref() == Some(table))
+    }
+
+    /// Whether every access to a table goes through an index
+    pub fn uses_index(&self, table: &str) -> bool {
+        let mut nodes = self
+            .nodes
+            .iter()
+            .filter(|node| node.table.as_deref() == Some(table))
+            .peekable();
+        nodes.peek().is_some() && nodes.all(|node| !node.is_full_scan())
+    }
+
+    /// Whether any table is read with a full scan
+    pub fn has_full_scan(&self) -> bool {
+        self.nodes.iter().any(PlanNode::is_full_scan)
+    }
+}
+
+// Every `"table": { "table_name": ... }` object in the JSON plan is an access
+fn collect_mysql_json(value: &Value, nodes: &mut Vec<PlanNode>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if key == "table" && value.get("table_name").is_some() {
+                    nodes.push(PlanNode {
+                        table: value["table_name"].as_str().map(str::to_string),
+                        access_type: value["access_type"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                        index: value["key"].as_str().map(str::to_string),
+                        rows: value["rows_examined_per_scan"].as_f64(),
+                        actual_rows: None,
+                        detail: value.to_string(),
+                    });
+                }
+                collect_mysql_json(value, nodes);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_mysql_json(value, nodes);
+            }
+        }
+        _ => {}
+    }
+}
+
+// -> Index lookup on u using idx_email (email='a')  (cost=0.35 rows=1) (actual time=0.02..0.02 rows=1 loops=1)
+fn parse_mysql_tree_line(line: &str) -> Option<PlanNode> {
+    let text = line.trim_start().strip_prefix("-> ")?;
+    let (access_type, rest) = text.split_once(" on ")?;
+    // "Filter: (a.b on ...)" and friends are not table accesses
+    if access_type.contains(':') || access_type.contains('(') {
+        return None;
+    }
+    let table = rest.split_whitespace().next().map(str::to_string);
+    let index = rest
+        .split_once(" using ")
+        .and_then(|(_, index)| index.split_whitespace().next())
+        .map(str::to_string);
+    let (rows, actual_rows) = match rest.split_once("(actual ") {
+        Some((estimate, actual)) => (find_rows(estimate), find_rows(actual)),
+        None => (find_rows(rest), None),
+    };
+    Some(PlanNode {
+        table,
+        access_type: access_type.to_string(),
+        index,
+        rows,
+        actual_rows,
+        detail: line.trim().to_string(),
+    })
+}
+
+fn find_rows(text: &str) -> Option<f64> {
+    let (_, rows) = text.split_once("rows=")?;
+    let end = rows
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == 'e' || c == '+'))
+        .unwrap_or(rows.len());
+    rows[..end].parse().ok()
+}
+
+// SEARCH users USING INDEX idx_email (email=?)
+// SCAN TABLE users (SQLite < 3.36)
+fn parse_sqlite_detail(detail: &str) -> Option<PlanNode> {
+    let mut words = detail.split_whitespace().peekable();
+    let access_type = words.next()?;
+    if access_type != "SCAN" && access_type != "SEARCH" {
+        return None;
+    }
+    if words.peek() == Some(&"TABLE") {
+        words.next();
+    }
+    let table = words.next()?;
+    if table == "CONSTANT" {
+        return None;
+    }
+    let index = match detail.split_once(" USING ") {
+        Some((_, using)) if using.starts_with("INTEGER PRIMARY KEY") => {
+            Some("INTEGER PRIMARY KEY".to_string())
+        }
+        Some((_, using)) => using
+            .split_once("INDEX ")
+            .and_then(|(_, index)| index.split_whitespace().next())
+            .filter(|index| !index.starts_with('('))
+            .map(str::to_string),
+        None => None,
+    };
+    Some(PlanNode {
+        table: Some(table.to_string()),
+        access_type: access_type.to_string(),
+        index,
+        rows: None,
+        actual_rows: None,
+        detail: detail.to_string(),
+    })
+}
@@ -0,0 +1,332 @@
+//! Typed SQL expressions for SELECT, WHERE, HAVING, ORDER BY and UPDATE SET
+//!
+//! Columns are quoted per dialect (`` `a`.`b` `` on MySQL, `"a"."b"` on SQLite)
+//! and values are always bound as `?` placeholders.
+//!
+//! ```rust
+//! use chain_builder::{ChainBuilder, Client, Expr, WhereClauses};
+//!
+//! let mut builder = ChainBuilder::new(Client::Mysql);
+//! builder
+//!     .table("orders")
+//!     .select_expr(Expr::col("price").mul(Expr::col("qty")), Some("total"))
+//!     .query(|qb| qb.where_expr(Expr::func("YEAR", vec![Expr::col("created_at")]).eq(Expr::val(2024))));
+//!
+//! let (sql, _) = builder.to_sql();
+//! assert_eq!(
+//!     sql,
+//!     "SELECT `price` * `qty` AS total FROM orders WHERE YEAR(`created_at`) = ?"
+//! );
+//! ```
+
+use crate::aggregate::Aggregate;
+use crate::builder::ChainBuilder;
+use crate::query::QueryBuilder;
+use crate::types::Client;
+use serde_json::Value;
+
+/// Binary operators usable in an `Expr`
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum BinaryOp {
+    /// +
+    Add,
+    /// -
+    Sub,
+    /// *
+    Mul,
+    /// /
+    Div,
+    /// %
+    Mod,
+    /// =
+    Eq,
+    /// !=
+    Ne,
+    /// <
+    Lt,
+    /// <=
+    Lte,
+    /// >
+    Gt,
+    /// >=
+    Gte,
+    /// LIKE
+    Like,
+    /// AND
+    And,
+    /// OR
+    Or,
+}
+
+impl BinaryOp {
+    /// SQL spelling of the operator
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Lte => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Gte => ">=",
+            BinaryOp::Like => "LIKE",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+        }
+    }
+}
+
+/// A SQL expression tree
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Expr {
+    /// Column reference, quoted per dialect (`*` and `table.*` are kept as-is)
+    Column(String),
+    /// Bound value
+    Value(Value),
+    /// Raw SQL with optional bind parameters
+    Raw(String, Option<Vec<Value>>),
+    /// Function call: name, arguments
+    Function(String, Vec<Expr>),
+    /// Binary operation: left, operator, right
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    /// NOT expr
+    Not(Box<Expr>),
+    /// IS NULL (IS NOT NULL when the flag is true)
+    IsNull(Box<Expr>, bool),
+    /// CASE WHEN condition THEN result ... ELSE result END
+    Case(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+    /// CAST(expr AS type)
+    Cast(Box<Expr>, String),
+    /// Scalar subquery
+    Subquery(Box<ChainBuilder>),
+    /// WHERE-style conditions built with a `QueryBuilder`, joined with AND
+    Condition(Box<QueryBuilder>),
+    /// Aggregate call with DISTINCT / FILTER
+    Aggregate(Box<Aggregate>),
+    /// GROUPING(column): 1 on subtotal rows of a rollup, 0 otherwise
+    Grouping(String),
+}
+
+impl Expr {
+    /// Column reference
+    pub fn col(column: &str) -> Expr {
+        Expr::Column(column.to_string())
+    }
+
+    /// Bound value
+    pub fn val(value: impl Into<Value>) -> Expr {
+        Expr::Value(value.into())
+    }
+
+    /// Raw SQL with optional bind parameters
+    pub fn raw(sql: &str, binds: Option<Vec<Value>>) -> Expr {
+        Expr::Raw(sql.to_string(), binds)
+    }
+
+    /// Function call, e.g. `Expr::func("COALESCE", vec![a, b])`
+    pub fn func(name: &str, args: Vec<Expr>) -> Expr {
+        Expr::Function(name.to_string(), args)
+    }
+
+    /// CASE expression from `(condition, result)` pairs
+    pub fn case(whens: Vec<(Expr, Expr)>, else_: Option<Expr>) -> Expr {
+        Expr::Case(whens, else_.map(Box::new))
+    }
+
+    /// CAST(expr AS sql_type)
+    pub fn cast(self, sql_type: &str) -> Expr {
+        Expr::Cast(Box::new(self), sql_type.to_string())
+    }
+
+    /// GROUPING(column), for telling `group_by_rollup` subtotal rows apart
+    pub fn grouping(column: &str) -> Expr {
+        Expr::Grouping(column.to_string())
+    }
+
+    /// Scalar subquery
+    pub fn subquery(chain_builder: ChainBuilder) -> Expr {
+        Expr::Subquery(Box::new(chain_builder))
+    }
+
+    /// Combine with another expression using `op`
+    pub fn binary(self, op: BinaryOp, rhs: Expr) -> Expr {
+        Expr::Binary(Box::new(self), op, Box::new(rhs))
+    }
+
+    /// self + rhs
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Add, rhs)
+    }
+
+    /// self - rhs
+    #[allow(clippy::should_implement_trait)]
+    pub fn sub(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Sub, rhs)
+    }
+
+    /// self * rhs
+    #[allow(clippy::should_implement_trait)]
+    pub fn mul(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Mul, rhs)
+    }
+
+    /// self / rhs
+    #[allow(clippy::should_implement_trait)]
+    pub fn div(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Div, rhs)
+    }
+
+    /// self = rhs
+    pub fn eq(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Eq, rhs)
+    }
+
+    /// self != rhs
+    pub fn ne(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Ne, rhs)
+    }
+
+    /// self < rhs
+    pub fn lt(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Lt, rhs)
+    }
+
+    /// self <= rhs
+    pub fn lte(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Lte, rhs)
+    }
+
+    /// self > rhs
+    pub fn gt(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Gt, rhs)
+    }
+
+    /// self >= rhs
+    pub fn gte(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Gte, rhs)
+    }
+
+    /// self LIKE rhs
+    pub fn like(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Like, rhs)
+    }
+
+    /// self AND rhs
+    pub fn and(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::And, rhs)
+    }
+
+    /// self OR rhs
+    pub fn or(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Or, rhs)
+    }
+
+    /// NOT self
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Expr {
+        Expr::Not(Box::new(self))
+    }
+
+    /// self IS NULL
+    pub fn is_null(self) -> Expr {
+        Expr::IsNull(Box::new(self), false)
+    }
+
+    /// self IS NOT NULL
+    pub fn is_not_null(self) -> Expr {
+        Expr::IsNull(Box::new(self), true)
+    }
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Self {
+        Expr::Value(value)
+    }
+}
+
+impl From<Case> for Expr {
+    fn from(case: Case) -> Self {
+        case.end()
+    }
+}
+
+/// Builder for `CASE WHEN ... THEN ... ELSE ... END`
+///
+/// Each `when` condition is written with the usual `WhereClauses` methods, and
+/// condition and result binds are emitted in the order they appear in the SQL.
+///
+/// ```rust
+/// use chain_builder::{ChainBuilder, Client, Expr, WhereClauses};
+/// use serde_json::Value;
+///
+/// let mut builder = ChainBuilder::new(Client::Mysql);
+/// let label = builder
+///     .case()
+///     .when(|qb| qb.where_eq("status", Value::from(1)), Value::from("active"))
+///     .else_(Value::from("inactive"));
+/// builder.table("users").select_expr(label.end(), Some("label"));
+///
+/// let (sql, binds) = builder.to_sql();
+/// assert_eq!(
+///     sql,
+///     "SELECT CASE WHEN status = ? THEN ? ELSE ? END AS label FROM users"
+/// );
+/// assert_eq!(binds, vec![Value::from(1), Value::from("active"), Value::from("inactive")]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Case {
+    client: Client,
+    whens: Vec<(Expr, Expr)>,
+    else_: Option<Expr>,
+}
+
+impl Case {
+    /// Start an empty CASE for the given client
+    pub fn new(client: Client) -> Case {
+        Case {
+            client,
+            whens: Vec::new(),
+            else_: None,
+        }
+    }
+
+    /// Add `WHEN <conditions> THEN value`
+    pub fn when(
+        mut self,
+        condition: impl FnOnce(&mut QueryBuilder),
+        value: impl Into<Expr>,
+    ) -> Case {
+        let mut query_builder = QueryBuilder::new(self.client.clone());
+        condition(&mut query_builder);
+        self.whens
+            .push((Expr::Condition(Box::new(query_builder)), value.into()));
+        self
+    }
+
+    /// Add `WHEN condition THEN value` with a typed condition
+    pub fn when_expr(mut self, condition: Expr, value: impl Into<Expr>) -> Case {
+        self.whens.push((condition, value.into()));
+        self
+    }
+
+    /// Set the ELSE result
+    pub fn else_(mut self, value: impl Into<Expr>) -> Case {
+        self.else_ = Some(value.into());
+        self
+    }
+
+    /// Finish the CASE expression
+    ///
+    /// Panics if no `when` was added, since `CASE END` is not valid SQL.
+    pub fn end(self) -> Expr {
+        if self.whens.is_empty() {
+            panic!("[Err] case: at least one when() is required");
+        }
+        Expr::case(self.whens, self.else_)
+    }
+}
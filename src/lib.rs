@@ -27,32 +27,63 @@
 //! ```
 
 // Core modules
+mod aggregate;
 mod builder;
 mod common;
+mod compiled;
+mod debug;
+mod explain;
+mod expr;
+mod policy;
 mod query;
+mod scope;
+mod template;
 mod types;
+mod visitor;
+mod writer;
 
 // Database-specific modules
 #[cfg(feature = "mysql")]
 mod mysql;
 #[cfg(feature = "sqlite")]
 mod sqlite;
+#[cfg(any(
+    all(feature = "mysql", feature = "sqlx_mysql"),
+    all(feature = "sqlite", feature = "sqlx_sqlite")
+))]
+mod sqlx_database;
 #[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
 mod sqlx_mysql;
 #[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
 mod sqlx_sqlite;
 
 // Re-export main types
+pub use aggregate::{Aggregate, AggregateFunc};
 pub use builder::ChainBuilder;
+pub use compiled::CompiledQuery;
+pub use debug::Redaction;
+pub use explain::{PlanNode, QueryPlan};
+pub use expr::{BinaryOp, Case, Expr};
 pub use query::{Operator, QueryBuilder};
-pub use types::{Client, Common, Method, Select, Statement};
-
-// Re-export database-specific types
-#[cfg(feature = "mysql")]
-pub use mysql::ToSql;
+pub use scope::{register_scope, Scope};
+#[cfg(any(
+    all(feature = "mysql", feature = "sqlx_mysql"),
+    all(feature = "sqlite", feature = "sqlx_sqlite")
+))]
+pub use sqlx_database::SqlxDatabase;
+pub use template::{Param, QueryTemplate};
+pub use types::{Client, Common, Method, MissingColumn, NullsOrder, RawBinds, Select, Statement};
+pub use visitor::{
+    walk_chain_builder, walk_chain_builder_mut, walk_common, walk_common_mut, walk_expr,
+    walk_expr_mut, walk_join, walk_join_mut, walk_join_statement, walk_join_statement_mut,
+    walk_query_builder, walk_query_builder_mut, walk_select, walk_select_mut, walk_statement,
+    walk_statement_mut, Visitor, VisitorMut,
+};
+pub use writer::SqlWriter;
 
 // Re-export join functionality
-pub use query::join::{JoinBuilder, JoinMethods};
+pub use query::join::{JoinBuilder, JoinMethods, JoinStatement};
 
 // Re-export query builder functionality
 pub use query::common::{HavingClauses, QueryCommon, WhereClauses};
+pub use query::conditional::Conditional;
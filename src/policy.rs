@@ -0,0 +1,347 @@
+//! Query policies enforced at compile time (soft delete, tenant)
+//!
+//! Policies are applied to a copy of the builder right before it is compiled,
+//! so nested builders (subqueries, EXISTS, CTEs, unions and INSERT ... SELECT
+//! sources) are covered and the original builder is left untouched. Nested
+//! builders inherit the policy of their parent unless they set their own.
+
+use crate::builder::ChainBuilder;
+use crate::expr::Expr;
+use crate::query::common::WhereClauses;
+use crate::query::join::{JoinBuilder, JoinStatement};
+use crate::query::QueryBuilder;
+use crate::types::{Common, Method, Select};
+use crate::visitor::{
+    walk_chain_builder, walk_chain_builder_mut, walk_common, walk_expr, walk_join_statement,
+    walk_query_builder, Visitor, VisitorMut,
+};
+use serde_json::Value;
+
+/// Which rows the soft-delete scope lets through
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) enum Trashed {
+    /// Only rows that are not soft-deleted
+    #[default]
+    Without,
+    /// All rows, the scope is disabled
+    With,
+    /// Only soft-deleted rows
+    Only,
+}
+
+impl ChainBuilder {
+    /// Enable the soft-delete scope on `column` (usually `deleted_at`)
+    ///
+    /// SELECT and UPDATE skip rows where the column is set, joined tables get
+    /// the same predicate in their ON clause, and `delete()` becomes
+    /// `UPDATE ... SET column = CURRENT_TIMESTAMP`.
+    pub fn soft_delete(&mut self, column: &str) -> &mut ChainBuilder {
+        self.soft_delete = Some(column.to_string());
+        self
+    }
+
+    /// Include soft-deleted rows (disables the scope for this builder)
+    pub fn with_trashed(&mut self) -> &mut ChainBuilder {
+        self.trashed = Trashed::With;
+        self
+    }
+
+    /// Only return soft-deleted rows of the main table
+    pub fn only_trashed(&mut self) -> &mut ChainBuilder {
+        self.trashed = Trashed::Only;
+        self
+    }
+
+    /// Restrict the query to one tenant
+    ///
+    /// Every table reference (main table, joins, CTEs, unions and subqueries)
+    /// gets `table.column = value`, and inserted rows get the column stamped.
+    /// Raw SQL fragments (`*_raw` methods, `on_raw`, `Expr::raw`) panic at
+    /// compile time because they could read other tenants' rows.
+    pub fn tenant(&mut self, column: &str, value: Value) -> &mut ChainBuilder {
+        self.tenant = Some((column.to_string(), value));
+        self
+    }
+
+    /// Really DELETE rows instead of soft-deleting them
+    pub fn force_delete(&mut self) -> &mut ChainBuilder {
+        self.method = Method::Delete;
+        self.force_delete = true;
+        self
+    }
+}
+
+// Apply every policy found in the tree to a copy of the builder
+pub(crate) fn apply(chain_builder: &ChainBuilder) -> Option<ChainBuilder> {
+    let mut finder = PolicyFinder(false);
+    finder.visit_chain_builder(chain_builder);
+    if !finder.0 {
+        return None;
+    }
+    let mut chain_builder = chain_builder.clone();
+    PolicyScope::default().visit_chain_builder_mut(&mut chain_builder);
+    Some(chain_builder)
+}
+
+struct PolicyFinder(bool);
+
+impl Visitor for PolicyFinder {
+    fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+        if chain_builder.soft_delete.is_some() || chain_builder.tenant.is_some() {
+            self.0 = true;
+            return;
+        }
+        walk_chain_builder(self, chain_builder);
+    }
+}
+
+// Policies inherited from the parent builders
+#[derive(Default)]
+struct PolicyScope {
+    soft_delete: Option<String>,
+    tenant: Option<(String, Value)>,
+    // CTE names in scope, filtered inside their own definition
+    ctes: Vec<String>,
+}
+
+impl VisitorMut for PolicyScope {
+    fn visit_chain_builder_mut(&mut self, chain_builder: &mut ChainBuilder) {
+        if chain_builder.soft_delete.is_none() {
+            chain_builder.soft_delete = self.soft_delete.clone();
+        }
+        if chain_builder.tenant.is_none() {
+            chain_builder.tenant = self.tenant.clone();
+        }
+        let ctes = self.ctes.len();
+        for common in chain_builder.query.query_common.iter() {
+            if let Common::With(alias, _, _) = common {
+                self.ctes.push(alias.clone());
+            }
+        }
+        if let Some((column, value)) = chain_builder.tenant.clone() {
+            scope_tenant(chain_builder, &column, &value, &self.ctes);
+        }
+        if let Some(column) = chain_builder.soft_delete.clone() {
+            scope_soft_delete(chain_builder, &column, &self.ctes);
+        }
+        let soft_delete =
+            std::mem::replace(&mut self.soft_delete, chain_builder.soft_delete.clone());
+        let tenant = std::mem::replace(&mut self.tenant, chain_builder.tenant.clone());
+        walk_chain_builder_mut(self, chain_builder);
+        self.soft_delete = soft_delete;
+        self.tenant = tenant;
+        self.ctes.truncate(ctes);
+    }
+}
+
+fn scope_tenant(chain_builder: &mut ChainBuilder, column: &str, value: &Value, ctes: &[String]) {
+    if chain_builder.table_raw.is_some() {
+        panic!("[Err] tenant: table_raw would bypass the tenant policy");
+    }
+    if !chain_builder.query.raw.is_empty() {
+        panic!("[Err] tenant: add_raw would bypass the tenant policy");
+    }
+    let mut raw = RawFragment::default();
+    raw.visit_chain_builder(chain_builder);
+    if let Some(kind) = raw.found {
+        panic!("[Err] tenant: {} would bypass the tenant policy", kind);
+    }
+    match chain_builder.method {
+        Method::Insert | Method::InsertMany => {
+            stamp_tenant(&mut chain_builder.insert_update, column, value);
+            if let Some((columns, source)) = &mut chain_builder.insert_select {
+                if columns.is_empty() {
+                    panic!("[Err] tenant: insert_from needs an explicit column list");
+                }
+                if !columns.iter().any(|c| c == column) {
+                    columns.push(column.to_string());
+                    // keep the source's own columns ahead of the tenant value
+                    if source.select.is_empty() {
+                        source.select.push(Select::Columns(vec!["*".to_string()]));
+                    }
+                    source
+                        .select
+                        .push(Select::Raw("?".to_string(), Some(vec![value.clone()])));
+                }
+            }
+        }
+        Method::Update => {
+            if matches!(chain_builder.insert_update.get(column), Some(v) if v != value) {
+                panic!("[Err] tenant: update would move rows to another tenant");
+            }
+            where_tenant(chain_builder, column, value, ctes);
+        }
+        Method::Select | Method::Delete => where_tenant(chain_builder, column, value, ctes),
+    }
+    let mut using = vec![];
+    for join in chain_builder.query.join.iter_mut() {
+        if join.raw.is_some() {
+            panic!("[Err] tenant: raw joins would bypass the tenant policy");
+        }
+        if ctes.contains(&join.table) {
+            continue;
+        }
+        let table = join.as_name.as_ref().unwrap_or(&join.table);
+        let qualified = format!("{}.{}", table, column);
+        if join.using.is_some() {
+            // an inner join, so filtering in WHERE is the same as in ON
+            using.push(qualified);
+            continue;
+        }
+        let predicate = JoinStatement::OnVal(qualified, "=".to_string(), value.clone());
+        push_join_predicate(join, predicate);
+    }
+    if !using.is_empty() {
+        chain_builder.query.group_statements();
+        for qualified in using {
+            chain_builder.query.where_eq(&qualified, value.clone());
+        }
+    }
+}
+
+// The first raw fragment in a builder, not counting its nested builders
+// (they are scoped, and checked, on their own)
+#[derive(Default)]
+struct RawFragment {
+    entered: bool,
+    found: Option<&'static str>,
+}
+
+impl Visitor for RawFragment {
+    fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+        if !self.entered {
+            self.entered = true;
+            walk_chain_builder(self, chain_builder);
+        }
+    }
+
+    fn visit_query_builder(&mut self, query_builder: &QueryBuilder) {
+        if query_builder.has_raw {
+            self.found.get_or_insert("raw select, where or having SQL");
+        }
+        walk_query_builder(self, query_builder);
+    }
+
+    fn visit_join_statement(&mut self, statement: &JoinStatement) {
+        if let JoinStatement::OnRaw(..) = statement {
+            self.found.get_or_insert("on_raw");
+        }
+        walk_join_statement(self, statement);
+    }
+
+    fn visit_common(&mut self, common: &Common) {
+        match common {
+            Common::GroupByRaw(..) => {
+                self.found.get_or_insert("group_by_raw");
+            }
+            Common::OrderByRaw(..) => {
+                self.found.get_or_insert("order_by_raw");
+            }
+            _ => {}
+        }
+        walk_common(self, common);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Raw(..) = expr {
+            self.found.get_or_insert("Expr::raw");
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn where_tenant(chain_builder: &mut ChainBuilder, column: &str, value: &Value, ctes: &[String]) {
+    if let Some(qualified) = qualify(chain_builder, column, ctes) {
+        chain_builder.query.group_statements();
+        chain_builder.query.where_eq(&qualified, value.clone());
+    }
+}
+
+// Rows without the tenant column get it, rows with another tenant are rejected
+fn stamp_tenant(data: &mut Value, column: &str, value: &Value) {
+    match data {
+        Value::Object(row) => match row.get(column) {
+            Some(v) if v != value => {
+                panic!("[Err] tenant: insert row belongs to another tenant");
+            }
+            Some(_) => {}
+            None => {
+                row.insert(column.to_string(), value.clone());
+            }
+        },
+        Value::Array(rows) => {
+            for row in rows.iter_mut() {
+                stamp_tenant(row, column, value);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn scope_soft_delete(chain_builder: &mut ChainBuilder, column: &str, ctes: &[String]) {
+    if chain_builder.trashed == Trashed::With {
+        return;
+    }
+    if chain_builder.method == Method::Delete && !chain_builder.force_delete {
+        if chain_builder.trashed == Trashed::Only {
+            // the rows are already deleted, stamping them again would lose the deletion time
+            panic!("[Err] soft_delete: only_trashed().delete() would re-stamp deleted rows, use force_delete()");
+        }
+        chain_builder.method = Method::Update;
+        chain_builder.insert_update = Value::Null;
+        chain_builder.update_raw(&format!("{} = CURRENT_TIMESTAMP", column), None);
+    }
+    if matches!(
+        chain_builder.method,
+        Method::Select | Method::Update | Method::Delete
+    ) {
+        if let Some(qualified) = qualify(chain_builder, column, ctes) {
+            let query = &mut chain_builder.query;
+            query.group_statements();
+            if chain_builder.trashed == Trashed::Only {
+                query.where_not_null(&qualified);
+            } else {
+                query.where_null(&qualified);
+            }
+        }
+    }
+    for join in chain_builder.query.join.iter_mut() {
+        // Raw and USING joins have no ON clause to extend
+        if join.raw.is_some() || join.using.is_some() || ctes.contains(&join.table) {
+            continue;
+        }
+        let table = join.as_name.as_ref().unwrap_or(&join.table);
+        let predicate = JoinStatement::OnRaw(format!("{}.{} IS NULL", table, column), None);
+        push_join_predicate(join, predicate);
+    }
+}
+
+// `table.column` for the main table, None when the table is a CTE
+// (only SELECT renders the alias, UPDATE and DELETE name the bare table)
+fn qualify(chain_builder: &ChainBuilder, column: &str, ctes: &[String]) -> Option<String> {
+    match &chain_builder.table {
+        Some(table) if ctes.contains(table) => None,
+        Some(table) if chain_builder.table_raw.is_none() => {
+            let table = match &chain_builder.as_name {
+                Some(alias) if chain_builder.method == Method::Select => alias,
+                _ => table,
+            };
+            Some(format!("{}.{}", table, column))
+        }
+        _ => Some(column.to_string()),
+    }
+}
+
+fn push_join_predicate(join: &mut JoinBuilder, predicate: JoinStatement) {
+    if join
+        .statement
+        .iter()
+        .any(|s| matches!(s, JoinStatement::OrChain(_) | JoinStatement::OnRaw(..)))
+    {
+        let mut group = join.clone();
+        group.statement = std::mem::take(&mut join.statement);
+        join.statement
+            .push(JoinStatement::SubChain(Box::new(group)));
+    }
+    join.statement.push(predicate);
+}
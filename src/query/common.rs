@@ -1,7 +1,9 @@
 //! Common query functionality for WHERE clauses and other query parts
 
+use crate::common::placeholder::named_binds;
+use crate::expr::Expr;
 use crate::query::QueryBuilder;
-use crate::types::Common;
+use crate::types::{Common, NullsOrder};
 use serde_json::Value;
 
 fn build_placeholders(len: usize) -> String {
@@ -42,11 +44,20 @@ pub trait QueryCommon {
     /// Add a raw GROUP BY clause
     fn group_by_raw(&mut self, sql: &str, binds: Option<Vec<Value>>);
 
+    /// GROUP BY with subtotal rows (`WITH ROLLUP` on MySQL, UNION ALL emulation on SQLite)
+    fn group_by_rollup(&mut self, columns: Vec<String>);
+
     /// Add an ORDER BY clause
     fn order_by(&mut self, column: &str, order: &str);
 
     /// Add a raw ORDER BY clause
     fn order_by_raw(&mut self, sql: &str, binds: Option<Vec<Value>>);
+
+    /// Add an ORDER BY clause with NULLS FIRST/LAST (emulated with `IS NULL` on MySQL)
+    fn order_by_nulls(&mut self, column: &str, order: &str, nulls: NullsOrder);
+
+    /// Add an ORDER BY clause on a typed expression
+    fn order_by_expr(&mut self, expr: Expr, order: &str);
 }
 
 impl QueryCommon for QueryBuilder {
@@ -85,6 +96,10 @@ impl QueryCommon for QueryBuilder {
             .push(Common::GroupByRaw(sql.to_string(), binds));
     }
 
+    fn group_by_rollup(&mut self, columns: Vec<String>) {
+        self.query_common.push(Common::GroupByRollup(columns));
+    }
+
     fn order_by(&mut self, column: &str, order: &str) {
         self.query_common
             .push(Common::OrderBy(column.to_string(), order.to_string()));
@@ -94,6 +109,19 @@ impl QueryCommon for QueryBuilder {
         self.query_common
             .push(Common::OrderByRaw(sql.to_string(), binds));
     }
+
+    fn order_by_nulls(&mut self, column: &str, order: &str, nulls: NullsOrder) {
+        self.query_common.push(Common::OrderByNulls(
+            column.to_string(),
+            order.to_string(),
+            nulls,
+        ));
+    }
+
+    fn order_by_expr(&mut self, expr: Expr, order: &str) {
+        self.query_common
+            .push(Common::OrderByExpr(expr, order.to_string()));
+    }
 }
 
 /// Trait for HAVING clause operations
@@ -104,6 +132,13 @@ pub trait HavingClauses {
     /// Add a HAVING condition with raw SQL
     fn having_raw(&mut self, sql: &str, binds: Option<Vec<Value>>);
 
+    /// Add a HAVING condition with raw SQL and `:name` binds
+    fn having_raw_named<'a>(
+        &mut self,
+        sql: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    );
+
     /// Add a HAVING BETWEEN condition
     fn having_between(&mut self, column: &str, values: [Value; 2]);
 
@@ -112,6 +147,9 @@ pub trait HavingClauses {
 
     /// Add a HAVING NOT IN condition
     fn having_not_in(&mut self, column: &str, values: Vec<Value>);
+
+    /// Add a HAVING condition from a typed expression
+    fn having_expr(&mut self, expr: Expr);
 }
 
 impl HavingClauses for QueryBuilder {
@@ -122,10 +160,21 @@ impl HavingClauses for QueryBuilder {
     }
 
     fn having_raw(&mut self, sql: &str, binds: Option<Vec<Value>>) {
+        self.has_raw = true;
         self.query_common
             .push(Common::Having(sql.to_string(), binds));
     }
 
+    fn having_raw_named<'a>(
+        &mut self,
+        sql: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    ) {
+        self.has_raw = true;
+        self.query_common
+            .push(Common::HavingNamed(sql.to_string(), named_binds(binds)));
+    }
+
     fn having_between(&mut self, column: &str, values: [Value; 2]) {
         let sql = format!("{} BETWEEN ? AND ?", column);
         self.query_common
@@ -153,6 +202,10 @@ impl HavingClauses for QueryBuilder {
         let sql = format!("{} NOT IN ({})", column, placeholders);
         self.query_common.push(Common::Having(sql, Some(values)));
     }
+
+    fn having_expr(&mut self, expr: Expr) {
+        self.query_common.push(Common::HavingExpr(expr));
+    }
 }
 
 /// Trait for WHERE clause operations
@@ -222,6 +275,140 @@ pub trait WhereClauses {
 
     /// Add a raw WHERE condition
     fn where_raw(&mut self, sql: &str, binds: Option<Vec<Value>>);
+
+    /// Add a raw WHERE condition with `:name` binds (a name may be reused)
+    fn where_raw_named<'a>(&mut self, sql: &str, binds: impl IntoIterator<Item = (&'a str, Value)>);
+
+    /// Add a WHERE condition from a typed expression
+    fn where_expr(&mut self, expr: Expr);
+
+    /// Add a group of conditions joined with AND: `(a AND b)`
+    fn and_group(&mut self, group: impl FnOnce(&mut QueryBuilder));
+
+    /// Add a group of conditions joined with OR: `(a OR b)`
+    fn or_group(&mut self, group: impl FnOnce(&mut QueryBuilder));
+
+    /// Add a negated group of conditions joined with AND: `NOT (a AND b)`
+    fn not_group(&mut self, group: impl FnOnce(&mut QueryBuilder));
+
+    /// OR an equality condition
+    fn or_where_eq(&mut self, column: &str, value: Value) {
+        self.or().where_eq(column, value);
+    }
+
+    /// OR a not equality condition
+    fn or_where_ne(&mut self, column: &str, value: Value) {
+        self.or().where_ne(column, value);
+    }
+
+    /// OR an IN condition
+    fn or_where_in(&mut self, column: &str, values: Vec<Value>) {
+        self.or().where_in(column, values);
+    }
+
+    /// OR a NOT IN condition
+    fn or_where_not_in(&mut self, column: &str, values: Vec<Value>) {
+        self.or().where_not_in(column, values);
+    }
+
+    /// OR an IS NULL condition
+    fn or_where_null(&mut self, column: &str) {
+        self.or().where_null(column);
+    }
+
+    /// OR an IS NOT NULL condition
+    fn or_where_not_null(&mut self, column: &str) {
+        self.or().where_not_null(column);
+    }
+
+    /// OR a BETWEEN condition
+    fn or_where_between(&mut self, column: &str, values: [Value; 2]) {
+        self.or().where_between(column, values);
+    }
+
+    /// OR a NOT BETWEEN condition
+    fn or_where_not_between(&mut self, column: &str, values: [Value; 2]) {
+        self.or().where_not_between(column, values);
+    }
+
+    /// OR a LIKE condition
+    fn or_where_like(&mut self, column: &str, value: Value) {
+        self.or().where_like(column, value);
+    }
+
+    /// OR a NOT LIKE condition
+    fn or_where_not_like(&mut self, column: &str, value: Value) {
+        self.or().where_not_like(column, value);
+    }
+
+    /// OR a case-insensitive LIKE condition
+    fn or_where_ilike(&mut self, column: &str, value: Value) {
+        self.or().where_ilike(column, value);
+    }
+
+    /// OR a greater than condition
+    fn or_where_gt(&mut self, column: &str, value: Value) {
+        self.or().where_gt(column, value);
+    }
+
+    /// OR a greater than or equal condition
+    fn or_where_gte(&mut self, column: &str, value: Value) {
+        self.or().where_gte(column, value);
+    }
+
+    /// OR a less than condition
+    fn or_where_lt(&mut self, column: &str, value: Value) {
+        self.or().where_lt(column, value);
+    }
+
+    /// OR a less than or equal condition
+    fn or_where_lte(&mut self, column: &str, value: Value) {
+        self.or().where_lte(column, value);
+    }
+
+    /// OR a column-to-column comparison
+    fn or_where_column(&mut self, lhs: &str, operator: &str, rhs: &str) {
+        self.or().where_column(lhs, operator, rhs);
+    }
+
+    /// OR an EXISTS condition
+    fn or_where_exists(&mut self, query: impl FnOnce(&mut crate::builder::ChainBuilder)) {
+        self.or().where_exists(query);
+    }
+
+    /// OR a NOT EXISTS condition
+    fn or_where_not_exists(&mut self, query: impl FnOnce(&mut crate::builder::ChainBuilder)) {
+        self.or().where_not_exists(query);
+    }
+
+    /// OR a JSON contains condition
+    fn or_where_json_contains(&mut self, column: &str, value: Value) {
+        self.or().where_json_contains(column, value);
+    }
+
+    /// OR a subquery condition
+    fn or_where_subquery(&mut self, query: impl FnOnce(&mut QueryBuilder)) {
+        self.or().where_subquery(query);
+    }
+
+    /// OR a raw WHERE condition
+    fn or_where_raw(&mut self, sql: &str, binds: Option<Vec<Value>>) {
+        self.or().where_raw(sql, binds);
+    }
+
+    /// OR a raw WHERE condition with `:name` binds
+    fn or_where_raw_named<'a>(
+        &mut self,
+        sql: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    ) {
+        self.or().where_raw_named(sql, binds);
+    }
+
+    /// OR a typed expression condition
+    fn or_where_expr(&mut self, expr: Expr) {
+        self.or().where_expr(expr);
+    }
 }
 
 impl WhereClauses for QueryBuilder {
@@ -352,10 +539,45 @@ impl WhereClauses for QueryBuilder {
     }
 
     fn where_raw(&mut self, sql: &str, binds: Option<Vec<Value>>) {
+        self.has_raw = true;
         self.statement
             .push(crate::types::Statement::Raw((sql.to_string(), binds)));
     }
 
+    fn where_raw_named<'a>(
+        &mut self,
+        sql: &str,
+        binds: impl IntoIterator<Item = (&'a str, Value)>,
+    ) {
+        self.has_raw = true;
+        self.statement.push(crate::types::Statement::RawNamed(
+            sql.to_string(),
+            named_binds(binds),
+        ));
+    }
+
+    fn where_expr(&mut self, expr: Expr) {
+        self.statement.push(crate::types::Statement::Expr(expr));
+    }
+
+    fn and_group(&mut self, group: impl FnOnce(&mut QueryBuilder)) {
+        self.where_subquery(group);
+    }
+
+    fn or_group(&mut self, group: impl FnOnce(&mut QueryBuilder)) {
+        let mut group_query = QueryBuilder::new(self.client.clone());
+        group(&mut group_query);
+        self.statement
+            .push(crate::types::Statement::OrGroup(Box::new(group_query)));
+    }
+
+    fn not_group(&mut self, group: impl FnOnce(&mut QueryBuilder)) {
+        let mut group_query = QueryBuilder::new(self.client.clone());
+        group(&mut group_query);
+        self.statement
+            .push(crate::types::Statement::NotGroup(Box::new(group_query)));
+    }
+
     fn where_ilike(&mut self, column: &str, value: Value) {
         // For MySQL, use LOWER() function
         let sql = format!("LOWER({}) LIKE LOWER(?)", column);
@@ -372,19 +594,17 @@ impl WhereClauses for QueryBuilder {
     fn where_exists(&mut self, query: impl FnOnce(&mut crate::builder::ChainBuilder)) {
         let mut sub_builder = crate::builder::ChainBuilder::new(self.client.clone());
         query(&mut sub_builder);
-        let (sub_sql, sub_binds) = sub_builder.to_sql();
-        let sql = format!("EXISTS ({})", sub_sql);
-        self.statement
-            .push(crate::types::Statement::Raw((sql, Some(sub_binds))));
+        self.statement.push(crate::types::Statement::Exists(
+            false,
+            Box::new(sub_builder),
+        ));
     }
 
     fn where_not_exists(&mut self, query: impl FnOnce(&mut crate::builder::ChainBuilder)) {
         let mut sub_builder = crate::builder::ChainBuilder::new(self.client.clone());
         query(&mut sub_builder);
-        let (sub_sql, sub_binds) = sub_builder.to_sql();
-        let sql = format!("NOT EXISTS ({})", sub_sql);
         self.statement
-            .push(crate::types::Statement::Raw((sql, Some(sub_binds))));
+            .push(crate::types::Statement::Exists(true, Box::new(sub_builder)));
     }
 
     fn where_json_contains(&mut self, column: &str, value: Value) {
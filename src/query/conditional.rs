@@ -0,0 +1,47 @@
+//! Conditional combinators for keeping builder chains fluent
+
+use crate::builder::ChainBuilder;
+use crate::query::join::JoinBuilder;
+use crate::query::QueryBuilder;
+
+/// Apply builder calls only when a condition holds
+///
+/// ```rust
+/// use chain_builder::{ChainBuilder, Client, Conditional, QueryCommon, WhereClauses};
+/// use serde_json::Value;
+///
+/// let status: Option<&str> = Some("active");
+/// let mut builder = ChainBuilder::new(Client::Mysql);
+/// builder.table("users").query(|qb| {
+///     qb.when_some(status, |qb, status| qb.where_eq("status", Value::from(status)))
+///         .unless(false, |qb| qb.limit(10));
+/// });
+/// ```
+pub trait Conditional {
+    /// Run `f` when `condition` is true
+    fn when(&mut self, condition: bool, f: impl FnOnce(&mut Self)) -> &mut Self {
+        if condition {
+            f(self);
+        }
+        self
+    }
+
+    /// Run `f` when `condition` is false
+    fn unless(&mut self, condition: bool, f: impl FnOnce(&mut Self)) -> &mut Self {
+        self.when(!condition, f)
+    }
+
+    /// Run `f` with the value when `value` is `Some`
+    fn when_some<T>(&mut self, value: Option<T>, f: impl FnOnce(&mut Self, T)) -> &mut Self {
+        if let Some(value) = value {
+            f(self, value);
+        }
+        self
+    }
+}
+
+impl Conditional for ChainBuilder {}
+
+impl Conditional for QueryBuilder {}
+
+impl Conditional for JoinBuilder {}
@@ -1,7 +1,9 @@
 //! JOIN methods for building JOIN clauses
 
 use super::{JoinBuilder, JoinStatement};
+use crate::common::placeholder::named_binds;
 use crate::query::QueryBuilder;
+use crate::types::RawBinds;
 use serde_json::Value;
 
 /// Trait for JOIN operations
@@ -35,6 +37,33 @@ pub trait JoinMethods {
 
     /// Add a raw JOIN clause
     fn raw_join(&mut self, raw: &str, val: Option<Vec<Value>>);
+
+    /// Add a raw JOIN clause with `:name` binds
+    fn raw_join_named<'a>(&mut self, raw: &str, binds: impl IntoIterator<Item = (&'a str, Value)>);
+}
+
+// Joining the same table under the same alias twice is an error in SQL,
+// so a join merged in again (by a scope or `merge`) is dropped when it is
+// identical to an existing one, and panics when its type or ON clauses differ,
+// since dropping it would lose that layer's conditions. Joins added directly
+// are never dropped.
+pub(crate) fn push_join(query: &mut QueryBuilder, join: JoinBuilder) {
+    if query.join.contains(&join) {
+        return;
+    }
+    if join.raw.is_none()
+        && query.join.iter().any(|existing| {
+            existing.raw.is_none()
+                && existing.table == join.table
+                && existing.as_name == join.as_name
+        })
+    {
+        panic!(
+            "[Err] merge: {} is already joined with a different type or ON clause",
+            join.table
+        );
+    }
+    query.join.push(join);
 }
 
 impl JoinMethods for QueryBuilder {
@@ -45,6 +74,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -57,6 +87,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "INNER JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -69,6 +100,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "LEFT JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -81,6 +113,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "RIGHT JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -93,6 +126,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "LEFT OUTER JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -105,6 +139,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "RIGHT OUTER JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -117,6 +152,7 @@ impl JoinMethods for QueryBuilder {
             join_type: "CROSS JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
@@ -129,15 +165,21 @@ impl JoinMethods for QueryBuilder {
             join_type: "FULL OUTER JOIN".into(),
             raw: None,
             as_name: None,
+            using: None,
         };
         on(&mut join);
         self.join.push(join);
     }
 
     fn join_using(&mut self, table: &str, columns: Vec<String>) {
-        let columns_str = columns.join(", ");
-        let sql = format!("JOIN {} USING ({})", table, columns_str);
-        self.raw_join(&sql, None);
+        self.join.push(JoinBuilder {
+            table: table.to_string(),
+            statement: vec![],
+            join_type: "JOIN".into(),
+            raw: None,
+            as_name: None,
+            using: Some(columns),
+        });
     }
 
     fn raw_join(&mut self, raw: &str, val: Option<Vec<Value>>) {
@@ -145,8 +187,20 @@ impl JoinMethods for QueryBuilder {
             table: raw.to_string(),
             statement: vec![],
             join_type: "".into(),
-            raw: Some((raw.to_string(), val)),
+            raw: Some((raw.to_string(), RawBinds::Positional(val))),
+            as_name: None,
+            using: None,
+        });
+    }
+
+    fn raw_join_named<'a>(&mut self, raw: &str, binds: impl IntoIterator<Item = (&'a str, Value)>) {
+        self.join.push(JoinBuilder {
+            table: raw.to_string(),
+            statement: vec![],
+            join_type: "".into(),
+            raw: Some((raw.to_string(), RawBinds::Named(named_binds(binds)))),
             as_name: None,
+            using: None,
         });
     }
 }
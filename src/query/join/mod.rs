@@ -8,12 +8,14 @@ use serde_json::Value;
 pub use join_methods::*;
 
 /// JOIN statement types
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub(crate) enum JoinStatement {
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub enum JoinStatement {
     /// Simple ON condition: left_column, operator, right_column
     On(String, String, String),
     /// OR chain for complex JOIN conditions
     OrChain(Box<JoinBuilder>),
+    /// Grouped conditions with AND logic
+    SubChain(Box<JoinBuilder>),
     /// ON condition with value: column, operator, value
     OnVal(String, String, Value),
     /// Raw ON condition with optional bind parameters
@@ -25,13 +27,14 @@ impl JoinStatement {
     pub fn as_mut_join_builder(&mut self) -> &mut JoinBuilder {
         match self {
             JoinStatement::OrChain(query) => query,
+            JoinStatement::SubChain(query) => query,
             _ => panic!("JoinStatement::as_mut_join_builder() called on non-chain statement"),
         }
     }
 }
 
 /// JOIN builder for constructing JOIN clauses
-#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct JoinBuilder {
     /// Table name to join
     pub(crate) table: String,
@@ -40,7 +43,42 @@ pub struct JoinBuilder {
     /// JOIN conditions
     pub(crate) statement: Vec<JoinStatement>,
     /// Raw JOIN SQL with optional bind parameters
-    pub(crate) raw: Option<(String, Option<Vec<Value>>)>,
+    pub(crate) raw: Option<(String, crate::types::RawBinds)>,
     /// Table alias
     pub(crate) as_name: Option<String>,
+    /// Columns of a `JOIN ... USING (...)`, which has no ON clause
+    #[serde(default)]
+    pub(crate) using: Option<Vec<String>>,
+}
+
+impl JoinBuilder {
+    /// Joined table name
+    pub fn table_name(&self) -> &str {
+        &self.table
+    }
+
+    /// JOIN keyword (`JOIN`, `LEFT JOIN`, ...)
+    pub fn join_type(&self) -> &str {
+        &self.join_type
+    }
+
+    /// Table alias, if set
+    pub fn alias(&self) -> Option<&str> {
+        self.as_name.as_deref()
+    }
+
+    /// USING columns, if joined with `join_using`
+    pub fn using_columns(&self) -> Option<&[String]> {
+        self.using.as_deref()
+    }
+
+    /// ON conditions
+    pub fn conditions(&self) -> &[JoinStatement] {
+        &self.statement
+    }
+
+    /// Mutable ON conditions
+    pub fn conditions_mut(&mut self) -> &mut Vec<JoinStatement> {
+        &mut self.statement
+    }
 }
@@ -1,10 +1,11 @@
 //! Query building functionality
 
 pub mod common;
+pub mod conditional;
 pub mod join;
 
-use crate::types::{Client, Common, Statement};
-use serde_json::Value;
+use crate::expr::Case;
+use crate::types::{Client, Common, RawBinds, Statement};
 
 /// Main query builder for constructing WHERE clauses and other query parts
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -12,13 +13,18 @@ pub struct QueryBuilder {
     /// WHERE clause statements
     pub(crate) statement: Vec<Statement>,
     /// Raw SQL statements
-    pub(crate) raw: Vec<(String, Option<Vec<Value>>)>,
+    pub(crate) raw: Vec<(String, RawBinds)>,
     /// JOIN clauses
     pub(crate) join: Vec<join::JoinBuilder>,
     /// Common clauses (WITH, UNION, LIMIT, etc.)
     pub(crate) query_common: Vec<Common>,
-    /// Database client type used for nested builders
+    /// Client handed to nested builders; compiling ignores it and uses the
+    /// dialect being compiled for, so nested builders follow `to_sql_for`
     pub(crate) client: Client,
+    /// Whether `where_raw`, `having_raw` or `select_raw` added SQL text
+    /// (rejected under a tenant policy)
+    #[serde(default)]
+    pub(crate) has_raw: bool,
 }
 
 impl QueryBuilder {
@@ -30,10 +36,111 @@ impl QueryBuilder {
             join: Vec::new(),
             query_common: Vec::new(),
             client,
+            has_raw: false,
         }
     }
+
+    /// Start a CASE expression for this builder's client
+    pub fn case(&self) -> Case {
+        Case::new(self.client.clone())
+    }
+
+    /// WHERE statements
+    pub fn statements(&self) -> &[Statement] {
+        &self.statement
+    }
+
+    /// Mutable WHERE statements
+    pub fn statements_mut(&mut self) -> &mut Vec<Statement> {
+        &mut self.statement
+    }
+
+    /// JOIN clauses
+    pub fn joins(&self) -> &[join::JoinBuilder] {
+        &self.join
+    }
+
+    /// Mutable JOIN clauses
+    pub fn joins_mut(&mut self) -> &mut Vec<join::JoinBuilder> {
+        &mut self.join
+    }
+
+    /// Raw SQL fragments with their bind parameters
+    pub fn raws(&self) -> &[(String, RawBinds)] {
+        &self.raw
+    }
+
+    /// Common clauses (WITH, UNION, LIMIT, etc.)
+    pub fn commons(&self) -> &[Common] {
+        &self.query_common
+    }
+
+    /// Mutable common clauses
+    pub fn commons_mut(&mut self) -> &mut Vec<Common> {
+        &mut self.query_common
+    }
+
+    /// Combine another builder into this one: `(self) AND (other)`
+    ///
+    /// Identical joins are added once (a join on the same table and alias with
+    /// another type or ON clause panics); raw SQL and common clauses are
+    /// appended after this builder's own, so binds keep their order.
+    pub fn merge(&mut self, mut other: QueryBuilder) -> &mut QueryBuilder {
+        let mut statement = std::mem::take(&mut other.statement);
+        if !statement.is_empty() {
+            self.group_statements();
+            if needs_group(&statement) {
+                let mut group = QueryBuilder::new(self.client.clone());
+                group.statement = statement;
+                statement = vec![Statement::SubChain(Box::new(group))];
+            }
+            self.statement.extend(statement);
+        }
+        self.merge_clauses(other);
+        self
+    }
+
+    /// Combine another builder into this one: `(self) OR (other)`
+    pub fn or_merge(&mut self, mut other: QueryBuilder) -> &mut QueryBuilder {
+        if !other.statement.is_empty() {
+            let mut group = QueryBuilder::new(self.client.clone());
+            group.statement = std::mem::take(&mut other.statement);
+            self.statement.push(Statement::OrChain(Box::new(group)));
+        }
+        self.merge_clauses(other);
+        self
+    }
+
+    fn merge_clauses(&mut self, other: QueryBuilder) {
+        self.has_raw |= other.has_raw;
+        for join in other.join {
+            join::push_join(self, join);
+        }
+        self.raw.extend(other.raw);
+        self.query_common.extend(other.query_common);
+    }
+
+    // `a OR b` AND `c` would compile to `a OR b AND c`, so wrap the
+    // statements in a group first. Raw statements may hide an OR too.
+    pub(crate) fn group_statements(&mut self) {
+        if needs_group(&self.statement) {
+            let mut group = QueryBuilder::new(self.client.clone());
+            group.statement = std::mem::take(&mut self.statement);
+            self.statement.push(Statement::SubChain(Box::new(group)));
+        }
+    }
+}
+
+fn needs_group(statement: &[Statement]) -> bool {
+    statement.iter().any(|s| {
+        matches!(
+            s,
+            Statement::OrChain(_) | Statement::Raw(_) | Statement::RawNamed(..)
+        )
+    })
 }
 
+// The client does not affect the compiled SQL, see `QueryBuilder::client`
 impl Default for QueryBuilder {
     fn default() -> Self {
         Self::new(Client::Mysql)
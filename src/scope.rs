@@ -0,0 +1,90 @@
+//! Reusable query scopes, applied directly or registered by name
+
+use crate::builder::ChainBuilder;
+use crate::query::join::push_join;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// A reusable query fragment (where clauses, joins, ordering, ...)
+///
+/// Closures taking `&mut ChainBuilder` are scopes. Parameterised scopes are
+/// plain structs, and a scope can compose others by calling `apply` or
+/// `scoped` on the builder it receives.
+///
+/// ```rust
+/// use chain_builder::{ChainBuilder, Client, Scope, WhereClauses};
+/// use serde_json::Value;
+///
+/// struct InRegion(&'static str);
+///
+/// impl Scope for InRegion {
+///     fn apply(&self, builder: &mut ChainBuilder) {
+///         let region = self.0;
+///         builder.query(|qb| qb.where_eq("region", Value::from(region)));
+///     }
+/// }
+///
+/// let mut builder = ChainBuilder::new(Client::Mysql);
+/// builder
+///     .table("users")
+///     .apply(InRegion("eu"))
+///     .apply(|b: &mut ChainBuilder| b.query(|qb| qb.where_null("deleted_at")));
+/// ```
+pub trait Scope {
+    /// Add this scope's clauses to the builder
+    fn apply(&self, builder: &mut ChainBuilder);
+}
+
+impl<F: Fn(&mut ChainBuilder)> Scope for F {
+    fn apply(&self, builder: &mut ChainBuilder) {
+        self(builder)
+    }
+}
+
+type SharedScope = Arc<dyn Scope + Send + Sync>;
+
+fn registry() -> &'static RwLock<HashMap<String, SharedScope>> {
+    static SCOPES: OnceLock<RwLock<HashMap<String, SharedScope>>> = OnceLock::new();
+    SCOPES.get_or_init(Default::default)
+}
+
+/// Register a scope under a name for `ChainBuilder::scoped`, replacing any previous one
+pub fn register_scope(name: &str, scope: impl Scope + Send + Sync + 'static) {
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.to_string(), Arc::new(scope));
+}
+
+impl ChainBuilder {
+    /// Apply a scope to this builder
+    pub fn apply(&mut self, scope: impl Scope) -> &mut ChainBuilder {
+        apply_scope(self, &scope);
+        self
+    }
+
+    /// Apply a scope registered with `register_scope`
+    pub fn scoped(&mut self, name: &str) -> &mut ChainBuilder {
+        // clone out of the lock so the scope can use `scoped` itself
+        let scope = registry()
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned();
+        match scope {
+            Some(scope) => apply_scope(self, scope.as_ref()),
+            None => panic!("[Err] scoped: no scope registered as {:?}", name),
+        }
+        self
+    }
+}
+
+// Joins a scope adds go through `push_join`, so an identical join is added once
+fn apply_scope(builder: &mut ChainBuilder, scope: &dyn Scope) {
+    let before = builder.query.join.len();
+    scope.apply(builder);
+    let added = builder.query.join.split_off(before);
+    for join in added {
+        push_join(&mut builder.query, join);
+    }
+}
@@ -0,0 +1,170 @@
+//! `GROUP BY ... WITH ROLLUP` emulation for SQLite
+//!
+//! `group_by_rollup(["a", "b"])` becomes one grouped query per level, from
+//! `GROUP BY a, b` down to the grand total, combined with UNION ALL:
+//!
+//! ```sql
+//! SELECT * FROM (
+//!   SELECT a, b, SUM(x) FROM t GROUP BY a, b
+//!   UNION ALL SELECT a, NULL AS b, SUM(x) FROM t GROUP BY a
+//!   UNION ALL SELECT NULL AS a, NULL AS b, SUM(x) FROM t
+//! ) ORDER BY ...
+//! ```
+//!
+//! Rolled-up columns in the SELECT list become `NULL AS column` and
+//! `GROUPING(column)` becomes `1` or `0`. ORDER BY, LIMIT/OFFSET, WITH and
+//! UNION apply to the combined result.
+
+use super::{tail_compiler, SqliteToSqlProvider};
+use crate::builder::ChainBuilder;
+use crate::common::clause_compiler::with_compiler;
+use crate::common::method_compiler::ToSqlProvider;
+use crate::expr::Expr;
+use crate::types::{Common, Method, Select};
+use crate::visitor::{walk_expr_mut, VisitorMut};
+use crate::writer::SqlWriter;
+
+/// Write `chain_builder` with the rollup emulation, if it has a rollup
+pub(super) fn emulate(
+    chain_builder: &ChainBuilder,
+    provider: &SqliteToSqlProvider,
+    writer: &mut dyn SqlWriter,
+) -> bool {
+    let columns = match rollup_columns(chain_builder) {
+        Some(columns) => columns,
+        None => return false,
+    };
+    if chain_builder.method != Method::Select {
+        panic!("[Err] group_by_rollup: only SELECT queries can be rolled up");
+    }
+
+    for column in columns.iter() {
+        if !chain_builder.select.iter().any(|s| selects(s, column)) {
+            panic!(
+                "[Err] group_by_rollup: {} must be selected as a column to be rolled up on SQLite",
+                column
+            );
+        }
+    }
+
+    // per-branch clauses; WITH, ORDER BY, LIMIT/OFFSET and UNION apply to the combined result
+    let mut base = chain_builder.clone();
+    base.query.query_common.retain(|c| {
+        matches!(
+            c,
+            Common::Having(..) | Common::HavingNamed(..) | Common::HavingExpr(_)
+        )
+    });
+
+    with_compiler(chain_builder, provider, writer);
+    writer.push_sql("SELECT * FROM (");
+    for kept in (0..=columns.len()).rev() {
+        let mut branch = base.clone();
+        if kept > 0 {
+            branch
+                .query
+                .query_common
+                .push(Common::GroupBy(columns[..kept].to_vec()));
+        }
+        let rolled_up = &columns[kept..];
+        for select in branch.select.iter_mut() {
+            null_rolled_up(select, rolled_up);
+        }
+        Grouping(rolled_up).visit_chain_builder_mut(&mut branch);
+
+        if kept < columns.len() {
+            writer.push_sql(" UNION ALL ");
+        }
+        provider.write_sql(&branch, writer);
+    }
+    writer.push_sql(")");
+    tail_compiler(chain_builder, provider, writer);
+    true
+}
+
+// Every GROUP BY column, in call order, when at least one rollup is present
+fn rollup_columns(chain_builder: &ChainBuilder) -> Option<Vec<String>> {
+    let commons = &chain_builder.query.query_common;
+    if !commons
+        .iter()
+        .any(|c| matches!(c, Common::GroupByRollup(_)))
+    {
+        return None;
+    }
+    let mut columns = vec![];
+    for common in commons.iter() {
+        match common {
+            Common::GroupBy(g) | Common::GroupByRollup(g) => columns.extend(g.iter().cloned()),
+            Common::GroupByRaw(..) => {
+                panic!("[Err] group_by_rollup: raw GROUP BY items cannot be rolled up on SQLite")
+            }
+            _ => {}
+        }
+    }
+    Some(columns)
+}
+
+// `users.region` -> `region`
+fn output_name(column: &str) -> &str {
+    column.rsplit('.').next().unwrap_or(column)
+}
+
+// `t.region` and `region` name the same column, `a.id` and `b.id` do not
+fn same_column(a: &str, b: &str) -> bool {
+    a == b || ((!a.contains('.') || !b.contains('.')) && output_name(a) == output_name(b))
+}
+
+fn is_rolled_up(rolled_up: &[String], column: &str) -> bool {
+    rolled_up.iter().any(|r| same_column(r, column))
+}
+
+// Whether the SELECT item outputs `column` as a plain column
+fn selects(select: &Select, column: &str) -> bool {
+    match select {
+        Select::Columns(columns) => columns.iter().any(|c| same_column(c, column)),
+        Select::Expr(Expr::Column(c), _) => same_column(c, column),
+        _ => false,
+    }
+}
+
+fn null_rolled_up(select: &mut Select, rolled_up: &[String]) {
+    match select {
+        Select::Columns(columns) => {
+            for column in columns.iter_mut() {
+                if is_rolled_up(rolled_up, column) {
+                    *column = format!("NULL AS {}", output_name(column));
+                }
+            }
+        }
+        Select::Expr(expr, alias) => {
+            if let Expr::Column(column) = expr {
+                if is_rolled_up(rolled_up, column) {
+                    *alias = Some(
+                        alias
+                            .take()
+                            .unwrap_or_else(|| output_name(column).to_string()),
+                    );
+                    *expr = Expr::raw("NULL", None);
+                }
+            }
+        }
+        Select::Raw(..) | Select::RawNamed(..) | Select::Builder(..) => {}
+    }
+}
+
+// GROUPING(column) -> 1 when the column is rolled up in this branch, 0 otherwise
+struct Grouping<'a>(&'a [String]);
+
+impl VisitorMut for Grouping<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Grouping(column) = expr {
+            let flag = if is_rolled_up(self.0, column) {
+                "1"
+            } else {
+                "0"
+            };
+            *expr = Expr::raw(flag, None);
+        }
+        walk_expr_mut(self, expr);
+    }
+}
@@ -0,0 +1,187 @@
+//! Binding values for the sqlx databases, shared by the sqlx backends
+
+use crate::builder::ChainBuilder;
+use crate::compiled::CompiledQuery;
+use crate::explain::QueryPlan;
+use crate::template::QueryTemplate;
+use crate::types::Client;
+use serde_json::Value;
+
+/// A sqlx database queries can be executed on (`sqlx::MySql`, `sqlx::Sqlite`)
+///
+/// Methods taking a pool infer it from the pool's type; the others take it
+/// as a type parameter, e.g. `compiled.to_sqlx_query::<sqlx::MySql>()`.
+pub trait SqlxDatabase: sqlx::Database {
+    /// Client the SQL must be compiled for
+    const CLIENT: Client;
+
+    /// Arguments holding the bind values
+    type BindArguments<'q>: sqlx::IntoArguments<'q, Self> + 'q;
+
+    /// Bind values with their native types
+    fn bind_arguments<'q>(binds: &[Value]) -> Self::BindArguments<'q>;
+
+    /// The `EXPLAIN` statement for `sql`
+    fn explain_sql(sql: &str, analyze: bool) -> String;
+
+    /// Parse the rows returned by `explain_sql`
+    fn query_plan(rows: &[Self::Row], analyze: bool) -> Result<QueryPlan, sqlx::Error>;
+}
+
+impl ChainBuilder {
+    /// Compile for `DB` and build a sqlx query borrowing the builder's SQL
+    ///
+    /// The database is a type parameter: `builder.to_sqlx_query::<sqlx::MySql>()`.
+    pub fn to_sqlx_query<DB: SqlxDatabase>(
+        &mut self,
+    ) -> sqlx::query::Query<'_, DB, DB::BindArguments<'_>> {
+        let (sql, binds) = self.to_sql_for(DB::CLIENT);
+        self.sql_str = sql;
+        sqlx::query_with(self.sql_str.as_str(), DB::bind_arguments(&binds))
+    }
+
+    /// Compile for `DB` and build a sqlx `query_as` borrowing the builder's SQL
+    pub fn to_sqlx_query_as<DB: SqlxDatabase, T>(
+        &mut self,
+    ) -> sqlx::query::QueryAs<'_, DB, T, DB::BindArguments<'_>>
+    where
+        T: for<'r> sqlx::FromRow<'r, DB::Row>,
+    {
+        let (sql, binds) = self.to_sql_for(DB::CLIENT);
+        self.sql_str = sql;
+        sqlx::query_as_with(self.sql_str.as_str(), DB::bind_arguments(&binds))
+    }
+
+    /// Run `EXPLAIN` for this query and parse the plan
+    ///
+    /// MySQL uses `EXPLAIN FORMAT=JSON`, SQLite `EXPLAIN QUERY PLAN`. The query
+    /// is compiled for the pool's database, like `build_for`.
+    pub async fn explain<DB: SqlxDatabase>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<QueryPlan, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+    {
+        self.run_explain(pool, false).await
+    }
+
+    /// Run `EXPLAIN ANALYZE` for this query and parse the plan (MySQL 8.0.18+)
+    ///
+    /// On MySQL the query is executed, so actual row counts are filled in.
+    /// SQLite has no EXPLAIN ANALYZE; this returns the `EXPLAIN QUERY PLAN` estimate.
+    pub async fn explain_analyze<DB: SqlxDatabase>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<QueryPlan, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+    {
+        self.run_explain(pool, true).await
+    }
+
+    async fn run_explain<DB: SqlxDatabase>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+        analyze: bool,
+    ) -> Result<QueryPlan, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+    {
+        // compiled for the pool's database, whatever client the builder was created with
+        let (sql, binds) = self.to_sql_for(DB::CLIENT);
+        let sql = DB::explain_sql(&sql, analyze);
+        let rows = sqlx::query_with(&sql, DB::bind_arguments(&binds))
+            .fetch_all(pool)
+            .await?;
+        DB::query_plan(&rows, analyze)
+    }
+}
+
+impl CompiledQuery {
+    fn sqlx_arguments<'q, DB: SqlxDatabase>(&self) -> DB::BindArguments<'q> {
+        if std::mem::discriminant(&self.dialect) != std::mem::discriminant(&DB::CLIENT) {
+            panic!(
+                "[Err] compiled query: built for {:?}, executed on {}",
+                self.dialect,
+                DB::NAME
+            );
+        }
+        DB::bind_arguments(&self.binds)
+    }
+
+    /// Build a sqlx query borrowing this compiled query
+    pub fn to_sqlx_query<DB: SqlxDatabase>(
+        &self,
+    ) -> sqlx::query::Query<'_, DB, DB::BindArguments<'_>> {
+        sqlx::query_with(self.sql.as_str(), self.sqlx_arguments::<DB>())
+    }
+
+    /// Build a sqlx `query_as` borrowing this compiled query
+    pub fn to_sqlx_query_as<DB: SqlxDatabase, T>(
+        &self,
+    ) -> sqlx::query::QueryAs<'_, DB, T, DB::BindArguments<'_>>
+    where
+        T: for<'r> sqlx::FromRow<'r, DB::Row>,
+    {
+        sqlx::query_as_with(self.sql.as_str(), self.sqlx_arguments::<DB>())
+    }
+
+    /// Execute the query and return the result summary
+    pub async fn execute<DB: SqlxDatabase>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<DB::QueryResult, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+    {
+        self.to_sqlx_query::<DB>().execute(pool).await
+    }
+
+    /// Fetch all rows as `T`
+    pub async fn fetch_all<DB: SqlxDatabase, T>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<Vec<T>, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+        T: for<'r> sqlx::FromRow<'r, DB::Row> + Send + Unpin,
+    {
+        self.to_sqlx_query_as::<DB, T>().fetch_all(pool).await
+    }
+
+    /// Fetch exactly one row as `T`
+    pub async fn fetch_one<DB: SqlxDatabase, T>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<T, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+        T: for<'r> sqlx::FromRow<'r, DB::Row> + Send + Unpin,
+    {
+        self.to_sqlx_query_as::<DB, T>().fetch_one(pool).await
+    }
+
+    /// Fetch at most one row as `T`
+    pub async fn fetch_optional<DB: SqlxDatabase, T>(
+        &self,
+        pool: &sqlx::Pool<DB>,
+    ) -> Result<Option<T>, sqlx::Error>
+    where
+        for<'c> &'c mut DB::Connection: sqlx::Executor<'c, Database = DB>,
+        T: for<'r> sqlx::FromRow<'r, DB::Row> + Send + Unpin,
+    {
+        self.to_sqlx_query_as::<DB, T>().fetch_optional(pool).await
+    }
+}
+
+impl QueryTemplate {
+    /// Bind the template's slots and build a sqlx query
+    pub fn to_sqlx_query<'a, DB: SqlxDatabase>(
+        &self,
+        params: impl IntoIterator<Item = (&'a str, Value)>,
+    ) -> sqlx::query::Query<'_, DB, DB::BindArguments<'_>> {
+        let (sql, binds) = self.bind(params);
+        sqlx::query_with(sql, DB::bind_arguments(&binds))
+    }
+}
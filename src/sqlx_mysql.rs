@@ -1,71 +1,50 @@
 use crate::builder::ChainBuilder;
+use crate::explain::QueryPlan;
+use crate::sqlx_database::SqlxDatabase;
+use crate::types::Client;
+use crate::writer::{push_raw_tokens, SqlWriter};
 use serde_json::Value;
-use sqlx::{self, mysql::MySqlArguments, Arguments, Row};
+use sqlx::{
+    self,
+    mysql::{MySqlArguments, MySqlRow},
+    Arguments, Row,
+};
+use std::fmt::Display;
 
-impl ChainBuilder {
-    #[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
-    fn value_to_arguments(&self, binds: &Vec<Value>) -> MySqlArguments {
-        let mut arguments: MySqlArguments = MySqlArguments::default();
-        for bind in binds {
-            match bind {
-                Value::Null => {
-                    // bind NULL อย่างชัดเจน
-                    let _ = arguments.add(Option::<String>::None);
-                }
-                Value::Bool(b) => {
-                    let _ = arguments.add(b);
-                }
-                Value::Number(n) => {
-                    if let Some(i) = n.as_i64() {
-                        let _ = arguments.add(i);
-                    } else if let Some(u) = n.as_u64() {
-                        if u <= i64::MAX as u64 {
-                            let _ = arguments.add(u as i64);
-                        } else {
-                            // ถ้าใหญ่เกิน เก็บเป็น string ปลอดภัยสุด
-                            let _ = arguments.add(u.to_string());
-                        }
-                    } else if let Some(f) = n.as_f64() {
-                        let _ = arguments.add(f);
-                    } else {
-                        let _ = arguments.add(n.to_string());
-                    }
-                }
-                Value::String(s) => {
-                    let _ = arguments.add(s);
-                }
-                Value::Array(arr) => {
-                    // SQLite ไม่มี array type → เก็บเป็น JSON text
-                    let _ = arguments.add(serde_json::to_string(&arr).unwrap_or_default());
-                }
-                Value::Object(obj) => {
-                    // เก็บเป็น JSON text
-                    let _ = arguments.add(serde_json::to_string(&obj).unwrap_or_default());
+// Bind a JSON value with its native MySQL type through `$target.$method(..)`;
+// arrays and objects are bound as JSON text
+macro_rules! bind_mysql_value {
+    ($target:expr, $method:ident, $value:expr) => {
+        match $value {
+            Value::Null => {
+                let _ = $target.$method(Option::<String>::None);
+            }
+            Value::Bool(b) => {
+                let _ = $target.$method(b);
+            }
+            Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    let _ = $target.$method(i);
+                } else if let Some(u) = n.as_u64() {
+                    // BIGINT UNSIGNED
+                    let _ = $target.$method(u);
+                } else if let Some(f) = n.as_f64() {
+                    let _ = $target.$method(f);
+                } else {
+                    let _ = $target.$method(n.to_string());
                 }
             }
+            Value::String(s) => {
+                let _ = $target.$method(s);
+            }
+            value => {
+                let _ = $target.$method(value.to_string());
+            }
         }
-        arguments
-    }
-
-    #[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
-    pub fn to_sqlx_query(
-        &mut self,
-    ) -> sqlx::query::Query<'_, sqlx::MySql, sqlx::mysql::MySqlArguments> {
-        let (_, binds) = self.to_sql();
-        sqlx::query_with(self.sql_str.as_str(), self.value_to_arguments(&binds))
-    }
-
-    #[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
-    pub fn to_sqlx_query_as<T>(
-        &mut self,
-    ) -> sqlx::query::QueryAs<'_, sqlx::MySql, T, sqlx::mysql::MySqlArguments>
-    where
-        T: for<'r> sqlx::FromRow<'r, sqlx::mysql::MySqlRow>,
-    {
-        let (_, binds) = self.to_sql();
-        sqlx::query_as_with(self.sql_str.as_str(), self.value_to_arguments(&binds))
-    }
+    };
+}
 
+impl ChainBuilder {
     #[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
     pub async fn count(
         &mut self,
@@ -75,9 +54,76 @@ impl ChainBuilder {
         let (_, binds) = self.to_sql();
         let sql = self.sql_str.as_str();
         let sql = format!("SELECT COUNT({}) FROM ({}) as count", column, sql);
-        let qb = sqlx::query_with(&sql, self.value_to_arguments(&binds));
+        let qb = sqlx::query_with(&sql, sqlx::MySql::bind_arguments(&binds));
         let query_count = qb.fetch_one(pool).await?;
         let count: i64 = query_count.try_get(0)?;
         Ok(count)
     }
 }
+
+impl SqlxDatabase for sqlx::MySql {
+    const CLIENT: Client = Client::Mysql;
+
+    type BindArguments<'q> = MySqlArguments;
+
+    fn bind_arguments<'q>(binds: &[Value]) -> Self::BindArguments<'q> {
+        let mut arguments = MySqlArguments::default();
+        for bind in binds {
+            bind_mysql_value!(arguments, add, bind.clone());
+        }
+        arguments
+    }
+
+    fn explain_sql(sql: &str, analyze: bool) -> String {
+        if analyze {
+            format!("EXPLAIN ANALYZE {}", sql)
+        } else {
+            format!("EXPLAIN FORMAT=JSON {}", sql)
+        }
+    }
+
+    fn query_plan(rows: &[MySqlRow], analyze: bool) -> Result<QueryPlan, sqlx::Error> {
+        let plan: String = match rows.first() {
+            Some(row) => row.try_get(0)?,
+            None => return Err(sqlx::Error::RowNotFound),
+        };
+        if analyze {
+            return Ok(QueryPlan::from_mysql_tree(&plan));
+        }
+        let plan: Value =
+            serde_json::from_str(&plan).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Ok(QueryPlan::from_mysql_json(&plan))
+    }
+}
+
+/// Stream a query into a `sqlx::QueryBuilder`, binds added with `push_bind` as native types
+#[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
+impl SqlWriter for sqlx::QueryBuilder<'_, sqlx::MySql> {
+    fn push_sql(&mut self, sql: &str) {
+        self.push(sql);
+    }
+
+    fn push_bind(&mut self, value: Value) {
+        bind_mysql_value!(self, push_bind, value);
+    }
+
+    fn push_raw(&mut self, sql: &str, binds: &[Value]) {
+        push_raw_tokens(self, sql, binds, true);
+    }
+}
+
+/// One item of a `separated` / `push_values` list, see `ChainBuilder::push_to_sqlx_separated`
+#[cfg(all(feature = "mysql", feature = "sqlx_mysql"))]
+impl<Sep: Display> SqlWriter for sqlx::query_builder::Separated<'_, '_, sqlx::MySql, Sep> {
+    fn push_sql(&mut self, sql: &str) {
+        self.push_unseparated(sql);
+    }
+
+    fn push_bind(&mut self, value: Value) {
+        bind_mysql_value!(self, push_bind_unseparated, value);
+    }
+
+    fn push_raw(&mut self, sql: &str, binds: &[Value]) {
+        push_raw_tokens(self, sql, binds, true);
+    }
+}
@@ -1,6 +1,48 @@
 use crate::builder::ChainBuilder;
+use crate::explain::QueryPlan;
+use crate::sqlx_database::SqlxDatabase;
+use crate::types::Client;
+use crate::writer::SqlWriter;
 use serde_json::Value;
-use sqlx::{self, sqlite::SqliteArguments, Arguments};
+use sqlx::{
+    self,
+    sqlite::{SqliteArguments, SqliteRow},
+    Arguments, Row,
+};
+use std::fmt::Display;
+
+// Bind a JSON value with its native SQLite type through `$target.$method(..)`;
+// arrays and objects are bound as JSON text
+macro_rules! bind_sqlite_value {
+    ($target:expr, $method:ident, $value:expr) => {
+        match $value {
+            Value::Null => {
+                let _ = $target.$method(Option::<String>::None);
+            }
+            Value::Bool(b) => {
+                let _ = $target.$method(b);
+            }
+            Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    let _ = $target.$method(i);
+                } else if let Some(u) = n.as_u64() {
+                    // SQLite integers are signed 64-bit, larger values stay exact as text
+                    let _ = $target.$method(u.to_string());
+                } else if let Some(f) = n.as_f64() {
+                    let _ = $target.$method(f);
+                } else {
+                    let _ = $target.$method(n.to_string());
+                }
+            }
+            Value::String(s) => {
+                let _ = $target.$method(s);
+            }
+            value => {
+                let _ = $target.$method(value.to_string());
+            }
+        }
+    };
+}
 
 impl ChainBuilder {
     /// Build SQL + args for SQLite (use with sqlx::query_with(&sql, args))
@@ -16,72 +58,60 @@ impl ChainBuilder {
 
         (sql, args)
     }
+}
 
-    #[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
-    pub fn to_sqlx_query(
-        &mut self,
-    ) -> sqlx::query::Query<'_, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'_>> {
-        let (_, binds) = self.to_sql();
-        sqlx::query_with(self.sql_str.as_str(), self.value_to_arguments(&binds))
-    }
+impl SqlxDatabase for sqlx::Sqlite {
+    const CLIENT: Client = Client::Sqlite;
 
-    #[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
-    pub fn to_sqlx_query_as<T>(
-        &mut self,
-    ) -> sqlx::query::QueryAs<'_, sqlx::Sqlite, T, sqlx::sqlite::SqliteArguments<'_>>
-    where
-        T: for<'r> sqlx::FromRow<'r, sqlx::sqlite::SqliteRow>,
-    {
-        let (_, binds) = self.to_sql();
-        sqlx::query_as_with(self.sql_str.as_str(), self.value_to_arguments(&binds))
-    }
+    type BindArguments<'q> = SqliteArguments<'q>;
 
-    #[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
-    fn value_to_arguments(&self, binds: &Vec<Value>) -> SqliteArguments<'static> {
-        let mut arguments: SqliteArguments<'static> = SqliteArguments::default();
+    fn bind_arguments<'q>(binds: &[Value]) -> SqliteArguments<'q> {
+        let mut arguments = SqliteArguments::default();
         for bind in binds {
             push_sqlite_arg(&mut arguments, bind.clone());
         }
         arguments
     }
+
+    // SQLite has no EXPLAIN ANALYZE, both use the query plan estimate
+    fn explain_sql(sql: &str, _analyze: bool) -> String {
+        format!("EXPLAIN QUERY PLAN {}", sql)
+    }
+
+    fn query_plan(rows: &[SqliteRow], _analyze: bool) -> Result<QueryPlan, sqlx::Error> {
+        let details = rows
+            .iter()
+            .map(|row| row.try_get::<String, _>("detail"))
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(QueryPlan::from_sqlite(details.iter().map(String::as_str)))
+    }
 }
 
 #[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
 fn push_sqlite_arg<'a>(arguments: &mut SqliteArguments<'a>, v: Value) {
-    match v {
-        Value::Null => {
-            // bind NULL อย่างชัดเจน
-            let _ = arguments.add(Option::<String>::None);
-        }
-        Value::Bool(b) => {
-            let _ = arguments.add(b);
-        }
-        Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                let _ = arguments.add(i);
-            } else if let Some(u) = n.as_u64() {
-                if u <= i64::MAX as u64 {
-                    let _ = arguments.add(u as i64);
-                } else {
-                    // ถ้าใหญ่เกิน เก็บเป็น string ปลอดภัยสุด
-                    let _ = arguments.add(u.to_string());
-                }
-            } else if let Some(f) = n.as_f64() {
-                let _ = arguments.add(f);
-            } else {
-                let _ = arguments.add(n.to_string());
-            }
-        }
-        Value::String(s) => {
-            let _ = arguments.add(s);
-        }
-        Value::Array(arr) => {
-            // SQLite ไม่มี array type → เก็บเป็น JSON text
-            let _ = arguments.add(serde_json::to_string(&arr).unwrap_or_default());
-        }
-        Value::Object(obj) => {
-            // เก็บเป็น JSON text
-            let _ = arguments.add(serde_json::to_string(&obj).unwrap_or_default());
-        }
+    bind_sqlite_value!(arguments, add, v);
+}
+
+/// Stream a query into a `sqlx::QueryBuilder`, binds added with `push_bind` as native types
+#[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
+impl SqlWriter for sqlx::QueryBuilder<'_, sqlx::Sqlite> {
+    fn push_sql(&mut self, sql: &str) {
+        self.push(sql);
+    }
+
+    fn push_bind(&mut self, value: Value) {
+        bind_sqlite_value!(self, push_bind, value);
+    }
+}
+
+/// One item of a `separated` / `push_values` list, see `ChainBuilder::push_to_sqlx_separated`
+#[cfg(all(feature = "sqlite", feature = "sqlx_sqlite"))]
+impl<Sep: Display> SqlWriter for sqlx::query_builder::Separated<'_, '_, sqlx::Sqlite, Sep> {
+    fn push_sql(&mut self, sql: &str) {
+        self.push_unseparated(sql);
+    }
+
+    fn push_bind(&mut self, value: Value) {
+        bind_sqlite_value!(self, push_bind_unseparated, value);
     }
 }
@@ -0,0 +1,168 @@
+//! Queries compiled once and re-bound with new values
+//!
+//! Put `Param("name")` where a value would go, compile the builder into a
+//! `QueryTemplate` once (it is `Send + Sync`, so it can live in a `static`),
+//! then `bind` fresh values per call without recompiling.
+//!
+//! ```rust
+//! use chain_builder::{ChainBuilder, Client, Param, QueryTemplate, WhereClauses};
+//! use serde_json::Value;
+//! use std::sync::LazyLock;
+//!
+//! static ACTIVE_BY_USER: LazyLock<QueryTemplate> = LazyLock::new(|| {
+//!     let mut builder = ChainBuilder::new(Client::Mysql);
+//!     builder.table("orders").query(|qb| {
+//!         qb.where_eq("user_id", Param("user_id").into());
+//!         qb.where_eq("status", Value::from("active"));
+//!     });
+//!     builder.to_template()
+//! });
+//!
+//! let (sql, binds) = ACTIVE_BY_USER.bind([("user_id", Value::from(42))]);
+//! assert_eq!(sql, "SELECT * FROM orders WHERE user_id = ? AND status = ?");
+//! assert_eq!(binds, vec![Value::from(42), Value::from("active")]);
+//! ```
+
+use crate::builder::ChainBuilder;
+use crate::writer::SqlWriter;
+use serde_json::Value;
+
+// Object key marking a bind as a template slot
+const PARAM_KEY: &str = "$chain_builder_param";
+
+/// A named template slot, used in place of a bind value
+///
+/// Converts into a marker `Value`, so it works anywhere a value is bound
+/// as-is (`where_eq`, `where_in`, `insert`, `Expr::val`, ...). Methods that
+/// rewrite the value first (e.g. `where_like` patterns) cannot carry a slot.
+/// Compiling a slot with anything but `to_template()` panics.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param<'a>(pub &'a str);
+
+impl From<Param<'_>> for Value {
+    fn from(param: Param<'_>) -> Self {
+        serde_json::json!({ PARAM_KEY: param.0 })
+    }
+}
+
+pub(crate) fn param_name(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(object) if object.len() == 1 => object.get(PARAM_KEY)?.as_str(),
+        _ => None,
+    }
+}
+
+fn reject_param(value: &Value) {
+    if let Some(name) = param_name(value) {
+        panic!(
+            "[Err] template: Param(\"{}\") can only be compiled with to_template()",
+            name
+        );
+    }
+}
+
+// Writer for every compile path but `to_template`, where a slot has no value
+pub(crate) struct RejectParams<'a>(pub(crate) &'a mut dyn SqlWriter);
+
+impl SqlWriter for RejectParams<'_> {
+    fn push_sql(&mut self, sql: &str) {
+        self.0.push_sql(sql);
+    }
+
+    fn push_bind(&mut self, value: Value) {
+        reject_param(&value);
+        self.0.push_bind(value);
+    }
+
+    fn push_raw(&mut self, sql: &str, binds: &[Value]) {
+        binds.iter().for_each(reject_param);
+        self.0.push_raw(sql, binds);
+    }
+
+    fn reserve(&mut self, sql: usize, binds: usize) {
+        self.0.reserve(sql, binds);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Slot {
+    Value(Value),
+    Param(String),
+}
+
+/// SQL compiled once, with named slots re-bound per execution
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryTemplate {
+    sql: String,
+    slots: Vec<Slot>,
+}
+
+impl QueryTemplate {
+    /// Compile a builder into a template
+    pub fn new(chain_builder: &ChainBuilder) -> QueryTemplate {
+        let mut compiled = (String::new(), vec![]);
+        chain_builder.write_template_sql(chain_builder.client.clone(), &mut compiled);
+        let (sql, binds) = compiled;
+        let slots = binds
+            .into_iter()
+            .map(|value| match param_name(&value) {
+                Some(name) => Slot::Param(name.to_string()),
+                None => Slot::Value(value),
+            })
+            .collect();
+        QueryTemplate { sql, slots }
+    }
+
+    /// The compiled SQL
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Slot names in bind order (a name reused in the query appears once)
+    pub fn params(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for slot in self.slots.iter() {
+            if let Slot::Param(name) = slot {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Fill the slots and return the SQL with its binds
+    ///
+    /// Panics if a slot has no value or a value matches no slot.
+    pub fn bind<'a>(
+        &self,
+        params: impl IntoIterator<Item = (&'a str, Value)>,
+    ) -> (&str, Vec<Value>) {
+        let params: Vec<(&str, Value)> = params.into_iter().collect();
+        if let Some((unknown, _)) = params
+            .iter()
+            .find(|(name, _)| !self.slots.contains(&Slot::Param(name.to_string())))
+        {
+            panic!("[Err] template: no slot named {}", unknown);
+        }
+        let binds = self
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                Slot::Value(value) => value.clone(),
+                Slot::Param(name) => match params.iter().find(|(n, _)| n == name) {
+                    Some((_, value)) => value.clone(),
+                    None => panic!("[Err] template: no value for slot {}", name),
+                },
+            })
+            .collect();
+        (&self.sql, binds)
+    }
+}
+
+impl ChainBuilder {
+    /// Compile into a `QueryTemplate`, see `Param`
+    pub fn to_template(&self) -> QueryTemplate {
+        QueryTemplate::new(self)
+    }
+}
@@ -1,7 +1,7 @@
 //! Core types and enums for the Chain Builder library
 
 use crate::query::QueryBuilder;
-use serde_json::Value;
+use serde_json::{Map, Value};
 
 /// Supported database clients
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -23,8 +23,18 @@ pub enum Statement {
     SubChain(Box<QueryBuilder>),
     /// Subquery with OR logic
     OrChain(Box<QueryBuilder>),
+    /// Group whose statements are joined with OR
+    OrGroup(Box<QueryBuilder>),
+    /// Negated group: NOT (...)
+    NotGroup(Box<QueryBuilder>),
     /// Raw SQL statement with optional bind parameters
     Raw((String, Option<Vec<Value>>)),
+    /// Raw SQL statement with `:name` binds, resolved when compiled
+    RawNamed(String, Map<String, Value>),
+    /// EXISTS subquery (NOT EXISTS when the flag is true)
+    Exists(bool, Box<crate::builder::ChainBuilder>),
+    /// Typed expression condition
+    Expr(crate::expr::Expr),
 }
 
 impl Statement {
@@ -33,11 +43,22 @@ impl Statement {
         match self {
             Statement::OrChain(query) => query,
             Statement::SubChain(query) => query,
+            Statement::OrGroup(query) => query,
+            Statement::NotGroup(query) => query,
             _ => panic!("Statement::to_query_builder() called on non-chain statement"),
         }
     }
 }
 
+/// Bind values of a raw table, join or trailing SQL fragment
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum RawBinds {
+    /// Values for the `?` placeholders, in order
+    Positional(Option<Vec<Value>>),
+    /// Values for the `:name` placeholders, resolved when compiled
+    Named(Map<String, Value>),
+}
+
 /// SQL operation methods
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
 pub enum Method {
@@ -53,15 +74,33 @@ pub enum Method {
     Delete,
 }
 
+/// How `insert_many` fills a column that is missing from a row
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq)]
+pub enum MissingColumn {
+    /// Bind NULL for the missing value
+    #[default]
+    Null,
+    /// Use the `DEFAULT` keyword; SQLite has none in VALUES, so rows are split into
+    /// one INSERT per run of rows sharing the same keys, leaving missing columns out
+    Default,
+    /// Panic when a row lacks one of the columns
+    Error,
+}
+
 /// SELECT clause types
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum Select {
     /// Column names to select
     Columns(Vec<String>),
     /// Raw SQL with optional bind parameters
     Raw(String, Option<Vec<Value>>),
+    /// Raw SQL with `:name` binds, resolved when compiled
+    RawNamed(String, Map<String, Value>),
     /// Subquery as a column
     Builder(String, crate::builder::ChainBuilder),
+    /// Typed expression with optional alias
+    Expr(crate::expr::Expr, Option<String>),
 }
 
 /// Common SQL clauses (WITH, UNION, LIMIT, etc.)
@@ -79,10 +118,29 @@ pub enum Common {
     GroupBy(Vec<String>),
     /// Raw GROUP BY clause
     GroupByRaw(String, Option<Vec<Value>>),
+    /// GROUP BY ... WITH ROLLUP (emulated with UNION ALL on SQLite)
+    GroupByRollup(Vec<String>),
     /// HAVING clause
     Having(String, Option<Vec<Value>>),
+    /// Raw HAVING clause with `:name` binds, resolved when compiled
+    HavingNamed(String, Map<String, Value>),
     /// ORDER BY clause
     OrderBy(String, String),
     /// Raw ORDER BY clause
     OrderByRaw(String, Option<Vec<Value>>),
+    /// ORDER BY clause with explicit NULL placement
+    OrderByNulls(String, String, NullsOrder),
+    /// ORDER BY a typed expression: expression, direction
+    OrderByExpr(crate::expr::Expr, String),
+    /// HAVING a typed expression
+    HavingExpr(crate::expr::Expr),
+}
+
+/// Where NULLs sort in an ORDER BY
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, PartialEq)]
+pub enum NullsOrder {
+    /// NULLS FIRST
+    First,
+    /// NULLS LAST
+    Last,
 }
@@ -0,0 +1,358 @@
+//! Visitors for inspecting and rewriting a query tree before compilation
+//!
+//! Every `visit_*` method defaults to the matching `walk_*` function, which
+//! visits the node's children. Override the methods you care about and call
+//! the `walk_*` function from them to keep descending into nested builders
+//! (subqueries, EXISTS, CTEs, unions, OR chains, INSERT ... SELECT sources and
+//! scalar subqueries and CASE conditions inside expressions).
+//!
+//! ```rust
+//! use chain_builder::{ChainBuilder, Client, Visitor, walk_chain_builder};
+//!
+//! #[derive(Default)]
+//! struct Tables(Vec<String>);
+//!
+//! impl Visitor for Tables {
+//!     fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+//!         if let Some(table) = chain_builder.table_name() {
+//!             self.0.push(table.to_string());
+//!         }
+//!         walk_chain_builder(self, chain_builder);
+//!     }
+//! }
+//!
+//! let mut builder = ChainBuilder::new(Client::Mysql);
+//! builder.table("users");
+//! let mut tables = Tables::default();
+//! tables.visit_chain_builder(&builder);
+//! assert_eq!(tables.0, vec!["users".to_string()]);
+//! ```
+
+use crate::builder::ChainBuilder;
+use crate::expr::Expr;
+use crate::query::join::{JoinBuilder, JoinStatement};
+use crate::query::QueryBuilder;
+use crate::types::{Common, Select, Statement};
+
+/// Read-only traversal of a query tree
+pub trait Visitor {
+    /// Visit a (possibly nested) `ChainBuilder`
+    fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+        walk_chain_builder(self, chain_builder);
+    }
+
+    /// Visit one entry of a SELECT list
+    fn visit_select(&mut self, select: &Select) {
+        walk_select(self, select);
+    }
+
+    /// Visit a `QueryBuilder` (the main query, an OR chain or a subquery group)
+    fn visit_query_builder(&mut self, query_builder: &QueryBuilder) {
+        walk_query_builder(self, query_builder);
+    }
+
+    /// Visit one WHERE statement
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    /// Visit one JOIN
+    fn visit_join(&mut self, join: &JoinBuilder) {
+        walk_join(self, join);
+    }
+
+    /// Visit one JOIN condition
+    fn visit_join_statement(&mut self, statement: &JoinStatement) {
+        walk_join_statement(self, statement);
+    }
+
+    /// Visit one common clause (WITH, UNION, LIMIT, ORDER BY, ...)
+    fn visit_common(&mut self, common: &Common) {
+        walk_common(self, common);
+    }
+
+    /// Visit a typed expression
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+/// Visit the SELECT list, INSERT ... SELECT source and query parts of a builder
+pub fn walk_chain_builder<V: Visitor + ?Sized>(visitor: &mut V, chain_builder: &ChainBuilder) {
+    for select in chain_builder.select.iter() {
+        visitor.visit_select(select);
+    }
+    if let Some((_, source)) = &chain_builder.insert_select {
+        visitor.visit_chain_builder(source);
+    }
+    for expr in chain_builder.set_expr.iter() {
+        visitor.visit_expr(expr);
+    }
+    visitor.visit_query_builder(&chain_builder.query);
+}
+
+/// Visit the subquery of a `Select::Builder` or the expression of a `Select::Expr`
+pub fn walk_select<V: Visitor + ?Sized>(visitor: &mut V, select: &Select) {
+    match select {
+        Select::Builder(_, chain_builder) => visitor.visit_chain_builder(chain_builder),
+        Select::Expr(expr, _) => visitor.visit_expr(expr),
+        Select::Columns(_) | Select::Raw(..) | Select::RawNamed(..) => {}
+    }
+}
+
+/// Visit the statements, joins and common clauses of a query builder
+pub fn walk_query_builder<V: Visitor + ?Sized>(visitor: &mut V, query_builder: &QueryBuilder) {
+    for statement in query_builder.statement.iter() {
+        visitor.visit_statement(statement);
+    }
+    for join in query_builder.join.iter() {
+        visitor.visit_join(join);
+    }
+    for common in query_builder.query_common.iter() {
+        visitor.visit_common(common);
+    }
+}
+
+/// Visit the nested builder of a chain, group or EXISTS statement, or its expression
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::SubChain(query_builder)
+        | Statement::OrChain(query_builder)
+        | Statement::OrGroup(query_builder)
+        | Statement::NotGroup(query_builder) => {
+            visitor.visit_query_builder(query_builder);
+        }
+        Statement::Exists(_, chain_builder) => visitor.visit_chain_builder(chain_builder),
+        Statement::Expr(expr) => visitor.visit_expr(expr),
+        Statement::Value(..) | Statement::Raw(_) | Statement::RawNamed(..) => {}
+    }
+}
+
+/// Visit the conditions of a join
+pub fn walk_join<V: Visitor + ?Sized>(visitor: &mut V, join: &JoinBuilder) {
+    for statement in join.statement.iter() {
+        visitor.visit_join_statement(statement);
+    }
+}
+
+/// Visit the nested join of an OR chain or group
+pub fn walk_join_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &JoinStatement) {
+    if let JoinStatement::OrChain(join) | JoinStatement::SubChain(join) = statement {
+        visitor.visit_join(join);
+    }
+}
+
+/// Visit the nested builder of a WITH or UNION clause, or the expression of an ORDER BY/HAVING
+pub fn walk_common<V: Visitor + ?Sized>(visitor: &mut V, common: &Common) {
+    match common {
+        Common::With(_, _, chain_builder) | Common::Union(_, chain_builder) => {
+            visitor.visit_chain_builder(chain_builder);
+        }
+        Common::OrderByExpr(expr, _) | Common::HavingExpr(expr) => visitor.visit_expr(expr),
+        _ => {}
+    }
+}
+
+/// Visit the child expressions and scalar subqueries of an expression
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Function(_, args) => {
+            for arg in args.iter() {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::Binary(lhs, _, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Not(inner) | Expr::IsNull(inner, _) | Expr::Cast(inner, _) => {
+            visitor.visit_expr(inner);
+        }
+        Expr::Case(whens, else_) => {
+            for (condition, result) in whens.iter() {
+                visitor.visit_expr(condition);
+                visitor.visit_expr(result);
+            }
+            if let Some(else_) = else_ {
+                visitor.visit_expr(else_);
+            }
+        }
+        Expr::Subquery(chain_builder) => visitor.visit_chain_builder(chain_builder),
+        Expr::Condition(query_builder) => visitor.visit_query_builder(query_builder),
+        Expr::Aggregate(aggregate) => {
+            if let Some(arg) = &aggregate.arg {
+                visitor.visit_expr(arg);
+            }
+            if let Some(filter) = &aggregate.filter {
+                visitor.visit_expr(filter);
+            }
+        }
+        Expr::Column(_) | Expr::Value(_) | Expr::Raw(..) | Expr::Grouping(_) => {}
+    }
+}
+
+/// Mutable traversal of a query tree, for rewriting queries in place
+pub trait VisitorMut {
+    /// Visit a (possibly nested) `ChainBuilder`
+    fn visit_chain_builder_mut(&mut self, chain_builder: &mut ChainBuilder) {
+        walk_chain_builder_mut(self, chain_builder);
+    }
+
+    /// Visit one entry of a SELECT list
+    fn visit_select_mut(&mut self, select: &mut Select) {
+        walk_select_mut(self, select);
+    }
+
+    /// Visit a `QueryBuilder` (the main query, an OR chain or a subquery group)
+    fn visit_query_builder_mut(&mut self, query_builder: &mut QueryBuilder) {
+        walk_query_builder_mut(self, query_builder);
+    }
+
+    /// Visit one WHERE statement
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    /// Visit one JOIN
+    fn visit_join_mut(&mut self, join: &mut JoinBuilder) {
+        walk_join_mut(self, join);
+    }
+
+    /// Visit one JOIN condition
+    fn visit_join_statement_mut(&mut self, statement: &mut JoinStatement) {
+        walk_join_statement_mut(self, statement);
+    }
+
+    /// Visit one common clause (WITH, UNION, LIMIT, ORDER BY, ...)
+    fn visit_common_mut(&mut self, common: &mut Common) {
+        walk_common_mut(self, common);
+    }
+
+    /// Visit a typed expression
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// Visit the SELECT list, INSERT ... SELECT source and query parts of a builder
+pub fn walk_chain_builder_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    chain_builder: &mut ChainBuilder,
+) {
+    for select in chain_builder.select.iter_mut() {
+        visitor.visit_select_mut(select);
+    }
+    if let Some((_, source)) = &mut chain_builder.insert_select {
+        visitor.visit_chain_builder_mut(source);
+    }
+    for expr in chain_builder.set_expr.iter_mut() {
+        visitor.visit_expr_mut(expr);
+    }
+    visitor.visit_query_builder_mut(&mut chain_builder.query);
+}
+
+/// Visit the subquery of a `Select::Builder` or the expression of a `Select::Expr`
+pub fn walk_select_mut<V: VisitorMut + ?Sized>(visitor: &mut V, select: &mut Select) {
+    match select {
+        Select::Builder(_, chain_builder) => visitor.visit_chain_builder_mut(chain_builder),
+        Select::Expr(expr, _) => visitor.visit_expr_mut(expr),
+        Select::Columns(_) | Select::Raw(..) | Select::RawNamed(..) => {}
+    }
+}
+
+/// Visit the statements, joins and common clauses of a query builder
+pub fn walk_query_builder_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    query_builder: &mut QueryBuilder,
+) {
+    for statement in query_builder.statement.iter_mut() {
+        visitor.visit_statement_mut(statement);
+    }
+    for join in query_builder.join.iter_mut() {
+        visitor.visit_join_mut(join);
+    }
+    for common in query_builder.query_common.iter_mut() {
+        visitor.visit_common_mut(common);
+    }
+}
+
+/// Visit the nested builder of a chain, group or EXISTS statement, or its expression
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::SubChain(query_builder)
+        | Statement::OrChain(query_builder)
+        | Statement::OrGroup(query_builder)
+        | Statement::NotGroup(query_builder) => {
+            visitor.visit_query_builder_mut(query_builder);
+        }
+        Statement::Exists(_, chain_builder) => visitor.visit_chain_builder_mut(chain_builder),
+        Statement::Expr(expr) => visitor.visit_expr_mut(expr),
+        Statement::Value(..) | Statement::Raw(_) | Statement::RawNamed(..) => {}
+    }
+}
+
+/// Visit the conditions of a join
+pub fn walk_join_mut<V: VisitorMut + ?Sized>(visitor: &mut V, join: &mut JoinBuilder) {
+    for statement in join.statement.iter_mut() {
+        visitor.visit_join_statement_mut(statement);
+    }
+}
+
+/// Visit the nested join of an OR chain or group
+pub fn walk_join_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    statement: &mut JoinStatement,
+) {
+    if let JoinStatement::OrChain(join) | JoinStatement::SubChain(join) = statement {
+        visitor.visit_join_mut(join);
+    }
+}
+
+/// Visit the nested builder of a WITH or UNION clause, or the expression of an ORDER BY/HAVING
+pub fn walk_common_mut<V: VisitorMut + ?Sized>(visitor: &mut V, common: &mut Common) {
+    match common {
+        Common::With(_, _, chain_builder) | Common::Union(_, chain_builder) => {
+            visitor.visit_chain_builder_mut(chain_builder);
+        }
+        Common::OrderByExpr(expr, _) | Common::HavingExpr(expr) => visitor.visit_expr_mut(expr),
+        _ => {}
+    }
+}
+
+/// Visit the child expressions and scalar subqueries of an expression
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Function(_, args) => {
+            for arg in args.iter_mut() {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Expr::Binary(lhs, _, rhs) => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        }
+        Expr::Not(inner) | Expr::IsNull(inner, _) | Expr::Cast(inner, _) => {
+            visitor.visit_expr_mut(inner);
+        }
+        Expr::Case(whens, else_) => {
+            for (condition, result) in whens.iter_mut() {
+                visitor.visit_expr_mut(condition);
+                visitor.visit_expr_mut(result);
+            }
+            if let Some(else_) = else_ {
+                visitor.visit_expr_mut(else_);
+            }
+        }
+        Expr::Subquery(chain_builder) => visitor.visit_chain_builder_mut(chain_builder),
+        Expr::Condition(query_builder) => visitor.visit_query_builder_mut(query_builder),
+        Expr::Aggregate(aggregate) => {
+            if let Some(arg) = &mut aggregate.arg {
+                visitor.visit_expr_mut(arg);
+            }
+            if let Some(filter) = &mut aggregate.filter {
+                visitor.visit_expr_mut(filter);
+            }
+        }
+        Expr::Column(_) | Expr::Value(_) | Expr::Raw(..) | Expr::Grouping(_) => {}
+    }
+}
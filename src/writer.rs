@@ -0,0 +1,300 @@
+//! Sinks the compiler writes SQL and binds into
+//!
+//! Every clause is appended to one `SqlWriter` in output order, so compiling
+//! into `(String, Vec<Value>)` allocates the SQL and the binds once (the
+//! builder reserves an estimate up front). Implement the trait to stream a
+//! query into something else, such as a `sqlx::QueryBuilder`.
+//!
+//! ```rust
+//! use chain_builder::{ChainBuilder, Client, SqlWriter, WhereClauses};
+//! use serde_json::Value;
+//!
+//! // numbered placeholders, e.g. for a driver that wants `$1`
+//! #[derive(Default)]
+//! struct Numbered {
+//!     sql: String,
+//!     binds: Vec<Value>,
+//! }
+//!
+//! impl SqlWriter for Numbered {
+//!     fn push_sql(&mut self, sql: &str) {
+//!         self.sql.push_str(sql);
+//!     }
+//!
+//!     fn push_bind(&mut self, value: Value) {
+//!         self.binds.push(value);
+//!         self.sql.push_str(&format!("${}", self.binds.len()));
+//!     }
+//! }
+//!
+//! let mut builder = ChainBuilder::new(Client::Mysql);
+//! builder.table("users").query(|qb| {
+//!     qb.where_eq("status", Value::from("active"));
+//!     qb.where_raw("age > ?", Some(vec![Value::from(18)]));
+//! });
+//! let mut numbered = Numbered::default();
+//! builder.write_sql(&mut numbered);
+//! assert_eq!(numbered.sql, "SELECT * FROM users WHERE status = $1 AND age > $2");
+//! ```
+
+use crate::builder::ChainBuilder;
+use crate::common::placeholder::{tokenize, Token};
+use crate::expr::Expr;
+use crate::query::join::JoinStatement;
+use crate::types::{Client, Common, RawBinds, Select, Statement};
+use crate::visitor::{
+    walk_chain_builder, walk_common, walk_expr, walk_join_statement, walk_select, walk_statement,
+    Visitor,
+};
+use serde_json::{Map, Value};
+use std::fmt::Display;
+
+/// Destination for compiled SQL and its binds
+pub trait SqlWriter {
+    /// Append SQL text
+    fn push_sql(&mut self, sql: &str);
+
+    /// Append a placeholder bound to `value`
+    fn push_bind(&mut self, value: Value);
+
+    /// Append a raw fragment whose `?` placeholders are bound to `binds`
+    ///
+    /// The default splits `sql` at its placeholders (skipping quoted strings
+    /// and comments) and calls `push_sql` / `push_bind`. The compiler checks
+    /// that there is one bind per placeholder before calling it.
+    fn push_raw(&mut self, sql: &str, binds: &[Value]) {
+        push_raw_tokens(self, sql, binds, false);
+    }
+
+    /// Capacity hint, called once before compiling (`sql` bytes, `binds` values)
+    fn reserve(&mut self, sql: usize, binds: usize) {
+        let _ = (sql, binds);
+    }
+}
+
+/// SQL with `?` placeholders and the binds in order, as returned by `to_sql`
+impl SqlWriter for (String, Vec<Value>) {
+    fn push_sql(&mut self, sql: &str) {
+        self.0.push_str(sql);
+    }
+
+    fn push_bind(&mut self, value: Value) {
+        self.0.push('?');
+        self.1.push(value);
+    }
+
+    fn push_raw(&mut self, sql: &str, binds: &[Value]) {
+        self.0.push_str(sql);
+        self.1.extend(binds.iter().cloned());
+    }
+
+    fn reserve(&mut self, sql: usize, binds: usize) {
+        self.0.reserve(sql);
+        self.1.reserve(binds);
+    }
+}
+
+impl ChainBuilder {
+    /// Append this query to a `sqlx::QueryBuilder`, compiled for its database
+    ///
+    /// Binds go through `push_bind` with native types, so the output can be
+    /// mixed with the caller's own `push` / `push_bind` calls.
+    pub fn push_to_sqlx<'args, DB>(&self, query_builder: &mut sqlx::QueryBuilder<'args, DB>)
+    where
+        DB: sqlx::Database,
+        sqlx::QueryBuilder<'args, DB>: SqlWriter,
+    {
+        self.write_sql_for(sqlx_client::<DB>(), query_builder);
+    }
+
+    /// Append this query as one `(subquery)` item of a sqlx `separated` / `push_values` list
+    pub fn push_to_sqlx_separated<'qb, 'args: 'qb, DB, Sep>(
+        &self,
+        separated: &mut sqlx::query_builder::Separated<'qb, 'args, DB, Sep>,
+    ) where
+        DB: sqlx::Database,
+        Sep: Display,
+        sqlx::query_builder::Separated<'qb, 'args, DB, Sep>: SqlWriter,
+    {
+        separated.push("(");
+        self.write_sql_for(sqlx_client::<DB>(), separated);
+        separated.push_unseparated(")");
+    }
+}
+
+fn sqlx_client<DB: sqlx::Database>() -> Client {
+    match DB::NAME {
+        "MySQL" => Client::Mysql,
+        "SQLite" => Client::Sqlite,
+        name => panic!("[Err] push_to_sqlx: unsupported database {}", name),
+    }
+}
+
+// `push_raw` through `push_sql` / `push_bind`; MySQL strings use backslash escapes
+pub(crate) fn push_raw_tokens<W: SqlWriter + ?Sized>(
+    writer: &mut W,
+    sql: &str,
+    binds: &[Value],
+    backslash_escapes: bool,
+) {
+    let mut values = binds.iter();
+    for token in tokenize(sql, backslash_escapes) {
+        match token {
+            Token::Sql(sql) => writer.push_sql(sql),
+            Token::Named(name) => {
+                writer.push_sql(":");
+                writer.push_sql(name);
+            }
+            Token::Positional => match values.next() {
+                Some(value) => writer.push_bind(value.clone()),
+                None => writer.push_sql("?"),
+            },
+        }
+    }
+}
+
+// Rough (SQL bytes, bind count) for `chain_builder`, used to reserve capacity
+pub(crate) fn size_hint(chain_builder: &ChainBuilder) -> (usize, usize) {
+    let mut hint = SizeHint::default();
+    hint.visit_chain_builder(chain_builder);
+    (hint.sql, hint.binds)
+}
+
+// Keywords, separators and placeholders are covered by the per-node constants
+#[derive(Default)]
+struct SizeHint {
+    sql: usize,
+    binds: usize,
+}
+
+impl SizeHint {
+    fn raw(&mut self, sql: &str, binds: Option<&Vec<Value>>) {
+        self.sql += sql.len() + 2;
+        self.binds += binds.map_or(0, |binds| binds.len());
+    }
+
+    fn named(&mut self, sql: &str, values: &Map<String, Value>) {
+        self.sql += sql.len() + 2;
+        self.binds += values.len();
+    }
+
+    fn raw_binds(&mut self, sql: &str, binds: &RawBinds) {
+        match binds {
+            RawBinds::Positional(binds) => self.raw(sql, binds.as_ref()),
+            RawBinds::Named(values) => self.named(sql, values),
+        }
+    }
+
+    fn data(&mut self, data: &Value) {
+        match data {
+            Value::Object(object) => {
+                self.sql += object.keys().map(|key| key.len() + 8).sum::<usize>();
+                self.binds += object.len();
+            }
+            Value::Array(rows) => rows.iter().for_each(|row| self.data(row)),
+            _ => {}
+        }
+    }
+}
+
+impl Visitor for SizeHint {
+    fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+        self.sql += 32;
+        self.sql += chain_builder.table.as_ref().map_or(0, |table| table.len());
+        self.sql += chain_builder.db.as_ref().map_or(0, |db| db.len() + 1);
+        if let Some((table, binds)) = &chain_builder.table_raw {
+            self.raw_binds(table, binds);
+        }
+        self.data(&chain_builder.insert_update);
+        if let Some(upsert) = &chain_builder.upsert {
+            self.data(upsert);
+        }
+        for join in chain_builder.query.join.iter() {
+            self.sql += join.join_type.len() + join.table.len() + 8;
+            if let Some((raw, binds)) = &join.raw {
+                self.raw_binds(raw, binds);
+            }
+        }
+        for (raw, binds) in chain_builder.query.raw.iter() {
+            self.raw_binds(raw, binds);
+        }
+        walk_chain_builder(self, chain_builder);
+    }
+
+    fn visit_select(&mut self, select: &Select) {
+        match select {
+            Select::Columns(columns) => {
+                self.sql += columns.iter().map(|column| column.len() + 2).sum::<usize>();
+            }
+            Select::Raw(sql, binds) => self.raw(sql, binds.as_ref()),
+            Select::RawNamed(sql, values) => self.named(sql, values),
+            Select::Builder(alias, _) => self.sql += alias.len() + 8,
+            Select::Expr(..) => {}
+        }
+        walk_select(self, select);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Value(field, _, value) => {
+                let binds = value.as_array().map_or(1, |values| values.len());
+                self.sql += field.len() + 12 + binds * 2;
+                self.binds += binds;
+            }
+            Statement::Raw((sql, binds)) => self.raw(sql, binds.as_ref()),
+            Statement::RawNamed(sql, values) => self.named(sql, values),
+            _ => self.sql += 8,
+        }
+        walk_statement(self, statement);
+    }
+
+    fn visit_join_statement(&mut self, statement: &JoinStatement) {
+        match statement {
+            JoinStatement::On(column, operator, column2) => {
+                self.sql += column.len() + operator.len() + column2.len() + 7;
+            }
+            JoinStatement::OnVal(column, operator, _) => {
+                self.sql += column.len() + operator.len() + 9;
+                self.binds += 1;
+            }
+            JoinStatement::OnRaw(raw, binds) => self.raw(raw, binds.as_ref()),
+            _ => self.sql += 8,
+        }
+        walk_join_statement(self, statement);
+    }
+
+    fn visit_common(&mut self, common: &Common) {
+        match common {
+            Common::GroupBy(columns) | Common::GroupByRollup(columns) => {
+                self.sql += columns.iter().map(|column| column.len() + 2).sum::<usize>() + 24;
+            }
+            Common::GroupByRaw(sql, binds)
+            | Common::OrderByRaw(sql, binds)
+            | Common::Having(sql, binds) => self.raw(sql, binds.as_ref()),
+            Common::HavingNamed(sql, values) => self.named(sql, values),
+            Common::OrderBy(column, order) | Common::OrderByNulls(column, order, _) => {
+                self.sql += column.len() * 2 + order.len() + 24;
+            }
+            Common::With(alias, ..) => self.sql += alias.len() + 24,
+            Common::Limit(_) | Common::Offset(_) => {
+                self.sql += 12;
+                self.binds += 1;
+            }
+            _ => self.sql += 16,
+        }
+        walk_common(self, common);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Column(column) | Expr::Grouping(column) => self.sql += column.len() + 12,
+            Expr::Value(_) => {
+                self.sql += 1;
+                self.binds += 1;
+            }
+            Expr::Raw(sql, binds) => self.raw(sql, binds.as_ref()),
+            _ => self.sql += 12,
+        }
+        walk_expr(self, expr);
+    }
+}
@@ -1,5 +1,7 @@
 use chain_builder::{
-    ChainBuilder, Client, HavingClauses, JoinMethods, QueryCommon, Select, WhereClauses,
+    register_scope, walk_chain_builder, walk_query_builder_mut, Aggregate, ChainBuilder, Client,
+    Conditional, Expr, HavingClauses, JoinMethods, NullsOrder, QueryBuilder, QueryCommon,
+    QueryPlan, Redaction, Scope, Select, Statement, Visitor, VisitorMut, WhereClauses,
 };
 use serde_json::Value;
 
@@ -166,3 +168,1125 @@ fn test_select_methods() {
     assert!(sql.contains("SUM(points)"));
     assert!(sql.contains("created_at AS joined_at"));
 }
+
+#[test]
+fn test_debug_sql() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["*".into()]))
+        .query(|qb| {
+            qb.where_eq("name", Value::String("O'Brien \\ Co".to_string()));
+            qb.where_eq("active", Value::Bool(true));
+            qb.where_null("deleted_at");
+            qb.where_raw("note != '?'", None);
+            qb.where_in("id", vec![Value::Number(1.into()), Value::Number(2.into())]);
+            qb.limit(10);
+        });
+
+    assert_eq!(
+        builder.to_debug_sql(),
+        "SELECT * FROM users WHERE name = 'O''Brien \\\\ Co' AND active = TRUE AND deleted_at IS NULL AND note != '?' AND id IN (1,2) LIMIT 10"
+    );
+}
+
+#[test]
+fn test_debug_sql_redaction() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").update(serde_json::json!({
+        "password": "hunter2",
+        "name": "John",
+    }));
+    builder.query(|qb| {
+        qb.where_eq("users.api_token", Value::String("abc".to_string()));
+        qb.where_raw(
+            "session = ?",
+            Some(vec![Value::String("sess_123".to_string())]),
+        );
+    });
+
+    let redaction = Redaction::new()
+        .column("password")
+        .column("api_token")
+        .predicate(|_, value| {
+            value
+                .as_str()
+                .map(|s| s.starts_with("sess_"))
+                .unwrap_or(false)
+        });
+    assert_eq!(
+        builder.to_debug_sql_with(&redaction),
+        "UPDATE users SET name = 'John', password = '***' WHERE users.api_token = '***' AND session = '***'"
+    );
+    // the builder itself is left untouched
+    assert_eq!(builder.to_sql().1[1], Value::String("hunter2".to_string()));
+}
+
+#[test]
+fn test_query_plan_mysql() {
+    let plan = serde_json::json!({
+        "query_block": {
+            "select_id": 1,
+            "nested_loop": [
+                { "table": { "table_name": "u", "access_type": "ALL", "rows_examined_per_scan": 1000 } },
+                { "table": { "table_name": "o", "access_type": "ref", "key": "idx_user_id", "rows_examined_per_scan": 3 } }
+            ]
+        }
+    });
+    let plan = QueryPlan::from_mysql_json(&plan);
+    assert_eq!(plan.nodes.len(), 2);
+    assert!(plan.has_full_scan());
+    assert!(!plan.uses_index("u"));
+    assert!(plan.uses_index("o"));
+    assert_eq!(
+        plan.node("o").unwrap().index.as_deref(),
+        Some("idx_user_id")
+    );
+    assert_eq!(plan.node("u").unwrap().rows, Some(1000.0));
+
+    let plan = QueryPlan::from_mysql_tree(
+        "-> Filter: (u.age > 18)  (cost=1.25 rows=3) (actual time=0.03..0.04 rows=2 loops=1)\n    -> Index lookup on u using idx_email (email='a@b.c')  (cost=0.35 rows=1) (actual time=0.02..0.02 rows=1 loops=1)",
+    );
+    assert_eq!(plan.nodes.len(), 1);
+    let node = plan.node("u").unwrap();
+    assert_eq!(node.access_type, "Index lookup");
+    assert_eq!(node.index.as_deref(), Some("idx_email"));
+    assert_eq!(node.rows, Some(1.0));
+    assert_eq!(node.actual_rows, Some(1.0));
+    assert!(plan.uses_index("u"));
+}
+
+#[derive(Default)]
+struct TableCollector(Vec<String>);
+
+impl Visitor for TableCollector {
+    fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+        if let Some(table) = chain_builder.table_name() {
+            self.0.push(table.to_string());
+        }
+        walk_chain_builder(self, chain_builder);
+    }
+
+    fn visit_join(&mut self, join: &chain_builder::JoinBuilder) {
+        self.0.push(join.table_name().to_string());
+    }
+}
+
+#[test]
+fn test_visitor_walks_nested_builders() {
+    let mut cte = ChainBuilder::new(Client::Mysql);
+    cte.table("recent_orders");
+    let mut archived = ChainBuilder::new(Client::Mysql);
+    archived.table("archived_users");
+    let mut sub = ChainBuilder::new(Client::Mysql);
+    sub.table("order_items").select_count("*");
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Builder("items".into(), sub))
+        .with("ro", cte)
+        .union(archived)
+        .query(|qb| {
+            qb.join("profiles", |join| {
+                join.on("users.id", "=", "profiles.user_id");
+            });
+            qb.where_exists(|sub| {
+                sub.table("orders");
+            });
+        });
+
+    let mut tables = TableCollector::default();
+    tables.visit_chain_builder(&builder);
+    assert_eq!(
+        tables.0,
+        vec![
+            "users",
+            "order_items",
+            "orders",
+            "profiles",
+            "recent_orders",
+            "archived_users"
+        ]
+    );
+}
+
+struct TenantFilter(i64);
+
+impl VisitorMut for TenantFilter {
+    fn visit_query_builder_mut(&mut self, query_builder: &mut QueryBuilder) {
+        query_builder.where_eq("tenant_id", Value::from(self.0));
+        walk_query_builder_mut(self, query_builder);
+    }
+
+    fn visit_statement_mut(&mut self, _statement: &mut Statement) {
+        // only top-level query builders get the filter
+    }
+}
+
+#[test]
+fn test_visitor_mut_rewrites_query() {
+    let mut sub = ChainBuilder::new(Client::Mysql);
+    sub.table("archived_users");
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["*".into()]))
+        .union(sub)
+        .query(|qb| {
+            qb.where_eq("active", Value::Bool(true));
+        });
+
+    TenantFilter(7).visit_chain_builder_mut(&mut builder);
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE active = ? AND tenant_id = ? UNION SELECT * FROM archived_users WHERE tenant_id = ?"
+    );
+    assert_eq!(
+        binds,
+        vec![Value::Bool(true), Value::from(7), Value::from(7)]
+    );
+    assert_eq!(builder.query_builder().statements().len(), 2);
+}
+
+#[test]
+fn test_conditional_combinators() {
+    let status: Option<&str> = Some("active");
+    let role: Option<&str> = None;
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["*".into()]))
+        .when(true, |b| {
+            b.distinct();
+        })
+        .query(|qb| {
+            qb.when_some(status, |qb, status| {
+                qb.where_eq("status", Value::from(status))
+            })
+            .when_some(role, |qb, role| qb.where_eq("role", Value::from(role)))
+            .unless(false, |qb| {
+                qb.join("profiles", |join| {
+                    join.on("users.id", "=", "profiles.user_id")
+                        .when(false, |join| {
+                            join.on_val("profiles.public", "=", Value::Bool(true));
+                        });
+                })
+            })
+            .when(true, |qb| qb.order_by("id", "DESC"))
+            .when(false, |qb| qb.limit(10));
+            qb.or()
+                .when_some(role, |qb, role| qb.where_eq("role", Value::from(role)));
+            qb.where_subquery(|qb| {
+                qb.when(false, |qb| qb.where_null("deleted_at"));
+            });
+        });
+
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "SELECT DISTINCT * FROM users JOIN profiles ON users.id = profiles.user_id WHERE status = ? ORDER BY id DESC"
+                .to_string(),
+            vec![Value::from("active")]
+        )
+    );
+}
+
+#[test]
+fn test_condition_groups() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["*".into()]))
+        .query(|qb| {
+            qb.where_eq("active", Value::Bool(true));
+            qb.or_group(|g| {
+                g.where_eq("role", Value::from("admin"));
+                g.where_gt("karma", Value::from(100));
+                g.and_group(|g| {
+                    g.where_not_null("verified_at");
+                    g.where_lt("strikes", Value::from(3));
+                });
+            });
+            qb.not_group(|g| {
+                g.or_group(|g| {
+                    g.where_eq("status", Value::from("banned"));
+                    g.where_eq("status", Value::from("deleted"));
+                });
+            });
+            qb.not_group(|g| {
+                g.where_like("email", Value::from("%@test.%"));
+                g.or_where_null("email");
+            });
+            qb.or_where_in("id", vec![Value::from(1), Value::from(2)]);
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE active = ? AND (role = ? OR karma > ? OR (verified_at IS NOT NULL AND strikes < ?)) AND NOT (status = ? OR status = ?) AND NOT (email LIKE ? OR email IS NULL) OR id IN (?,?)"
+    );
+    assert_eq!(binds.len(), 9);
+}
+
+struct WithRegion(&'static str);
+
+impl Scope for WithRegion {
+    fn apply(&self, builder: &mut ChainBuilder) {
+        let region = self.0;
+        builder.query(|qb| {
+            qb.join("regions", |join| {
+                join.on("regions.id", "=", "users.region_id");
+            });
+            qb.where_eq("regions.code", Value::from(region));
+        });
+    }
+}
+
+#[test]
+fn test_scopes() {
+    register_scope("active", |builder: &mut ChainBuilder| {
+        builder.query(|qb| {
+            qb.where_eq("users.status", Value::from("active"));
+            qb.order_by("users.created_at", "DESC");
+        });
+    });
+    register_scope("active_in_eu", |builder: &mut ChainBuilder| {
+        builder.scoped("active").apply(WithRegion("eu"));
+    });
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["users.*".into()]))
+        .scoped("active_in_eu")
+        .apply(WithRegion("us"));
+
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT users.* FROM users JOIN regions ON regions.id = users.region_id WHERE users.status = ? AND regions.code = ? AND regions.code = ? ORDER BY users.created_at DESC"
+    );
+}
+
+#[test]
+fn test_repeated_joins_outside_scopes() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| {
+        qb.left_join("regions", |join| {
+            join.on("regions.id", "=", "users.home_region_id");
+        });
+        qb.join("regions", |join| {
+            join.on("regions.id", "=", "users.region_id");
+        });
+    });
+    // the scope's join is identical to the second one, so it is added once
+    builder.apply(WithRegion("eu"));
+
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT * FROM users LEFT JOIN regions ON regions.id = users.home_region_id JOIN regions ON regions.id = users.region_id WHERE regions.code = ?"
+    );
+}
+
+#[test]
+#[should_panic(expected = "[Err] merge: u is already joined with a different type or ON clause")]
+fn test_merge_conflicting_joins() {
+    let mut restricted = QueryBuilder::new(Client::Mysql);
+    restricted.join("u", |join| {
+        join.on("u.id", "=", "t.uid")
+            .on_val("u.active", "=", Value::Bool(true));
+    });
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("t").query(|qb| {
+        qb.left_join("u", |join| {
+            join.on("u.id", "=", "t.uid");
+        });
+    });
+    // dropping the merged join would lose `u.active = ?`
+    builder.and_where_builder(restricted);
+}
+
+#[test]
+#[should_panic(expected = "no scope registered")]
+fn test_unknown_scope() {
+    ChainBuilder::new(Client::Mysql).scoped("missing");
+}
+
+#[test]
+fn test_merge_query_builders() {
+    // auth layer
+    let mut auth = QueryBuilder::new(Client::Mysql);
+    auth.join("memberships", |join| {
+        join.on("memberships.user_id", "=", "users.id");
+    });
+    auth.where_eq("memberships.org_id", Value::from(1));
+    auth.or_where_eq("users.is_admin", Value::Bool(true));
+
+    // user filter
+    let mut filter = QueryBuilder::new(Client::Mysql);
+    filter.join("memberships", |join| {
+        join.on("memberships.user_id", "=", "users.id");
+    });
+    filter.where_like("users.name", Value::from("jo%"));
+    filter.order_by("users.name", "ASC");
+
+    // feature flag
+    let mut flag = QueryBuilder::new(Client::Mysql);
+    flag.where_eq("users.beta", Value::Bool(true));
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["users.*".into()]))
+        .and_where_builder(auth)
+        .and_where_builder(filter)
+        .or_where_builder(flag);
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT users.* FROM users JOIN memberships ON memberships.user_id = users.id WHERE (memberships.org_id = ? OR users.is_admin = ?) AND users.name LIKE ? OR users.beta = ? ORDER BY users.name ASC"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from(1),
+            Value::Bool(true),
+            Value::from("jo%"),
+            Value::Bool(true)
+        ]
+    );
+}
+
+#[test]
+fn test_clear_clauses() {
+    let mut listing = ChainBuilder::new(Client::Mysql);
+    listing
+        .table("users")
+        .select_distinct(vec!["id".into(), "name".into()])
+        .query(|qb| {
+            qb.left_join("profiles", |join| {
+                join.on("profiles.user_id", "=", "users.id");
+            });
+            qb.where_eq("status", Value::from("active"));
+            qb.order_by("name", "ASC");
+            qb.limit(20);
+            qb.offset(40);
+        });
+
+    let mut count = listing.clone();
+    count
+        .replace_select(Select::Raw("COUNT(*) AS total".into(), None))
+        .clear_order_by()
+        .clear_limit_offset()
+        .clear_joins();
+    assert_eq!(
+        count.to_sql(),
+        (
+            "SELECT COUNT(*) AS total FROM users WHERE status = ?".to_string(),
+            vec![Value::from("active")]
+        )
+    );
+
+    let mut export = listing.clone();
+    export.clear_select().clear_where().clear_limit_offset();
+    assert_eq!(
+        export.to_sql().0,
+        "SELECT * FROM users LEFT JOIN profiles ON profiles.user_id = users.id ORDER BY name ASC"
+    );
+
+    // the base query keeps everything
+    assert!(listing.to_sql().0.ends_with("LIMIT ? OFFSET ?"));
+}
+
+#[test]
+fn test_clear_expr_clauses() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("orders").query(|qb| {
+        qb.group_by(vec!["customer_id".into()]);
+        qb.having_expr(
+            Expr::func("COUNT", vec![Expr::col("*")])
+                .gt(Expr::val(2))
+                .or(Expr::func("SUM", vec![Expr::col("total")]).gt(Expr::val(100))),
+        );
+        qb.having("MAX(total)", "<", Value::from(500));
+        qb.order_by_expr(Expr::col("price").sub(Expr::col("discount")), "DESC");
+    });
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT * FROM orders GROUP BY customer_id HAVING (COUNT(*) > ? OR SUM(`total`) > ?) AND MAX(total) < ? ORDER BY `price` - `discount` DESC"
+    );
+
+    builder.clear_having().clear_order_by();
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "SELECT * FROM orders GROUP BY customer_id".to_string(),
+            vec![]
+        )
+    );
+}
+
+#[test]
+fn test_expr_not_operand() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| {
+        qb.where_expr(Expr::col("active").not().eq(Expr::col("banned")));
+        qb.where_expr(Expr::col("active").not().and(Expr::col("banned")));
+    });
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT * FROM users WHERE (NOT `active`) = `banned` AND NOT `active` AND `banned`"
+    );
+}
+
+#[test]
+fn test_mixed_order_and_group_by() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .select(Select::Columns(vec!["status".into()]))
+        .query(|qb| {
+            qb.group_by(vec!["status".into()]);
+            qb.group_by_raw("DATE(created_at)", None);
+            qb.group_by_raw("FLOOR(total / ?)", Some(vec![Value::from(100)]));
+            qb.order_by("status", "ASC");
+            qb.order_by_raw(
+                "FIELD(status, ?, ?)",
+                Some(vec![Value::from("new"), Value::from("paid")]),
+            );
+            qb.order_by_nulls("shipped_at", "DESC", NullsOrder::Last);
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT status FROM orders GROUP BY status, DATE(created_at), FLOOR(total / ?) ORDER BY status ASC, FIELD(status, ?, ?), shipped_at IS NULL ASC, shipped_at DESC"
+    );
+    assert_eq!(
+        binds,
+        vec![Value::from(100), Value::from("new"), Value::from("paid")]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("orders").query(|qb| {
+        qb.order_by_raw("RANDOM()", None);
+        qb.order_by_nulls("shipped_at", "ASC", NullsOrder::First);
+    });
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT * FROM orders ORDER BY RANDOM(), shipped_at ASC NULLS FIRST"
+    );
+}
+
+#[test]
+fn test_expr_in_select_where_having_order() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .select(Select::Columns(vec!["customer_id".into()]))
+        .select_expr(
+            Expr::func("SUM", vec![Expr::col("o.price").mul(Expr::col("o.qty"))]),
+            Some("total"),
+        )
+        .query(|qb| {
+            qb.where_expr(
+                Expr::col("status")
+                    .eq(Expr::val("paid"))
+                    .or(Expr::col("status").eq(Expr::val("shipped"))),
+            );
+            qb.where_expr(Expr::col("deleted_at").is_null());
+            qb.group_by(vec!["customer_id".into()]);
+            qb.having_expr(Expr::func("COUNT", vec![Expr::col("*")]).gt(Expr::val(2)));
+            qb.order_by_expr(Expr::col("price").sub(Expr::col("discount")), "DESC");
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT customer_id, SUM(`o`.`price` * `o`.`qty`) AS total FROM orders WHERE (`status` = ? OR `status` = ?) AND `deleted_at` IS NULL GROUP BY customer_id HAVING COUNT(*) > ? ORDER BY `price` - `discount` DESC"
+    );
+    assert_eq!(
+        binds,
+        vec![Value::from("paid"), Value::from("shipped"), Value::from(2)]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("products")
+        .select_expr(Expr::col("price").cast("INTEGER"), Some("whole"))
+        .query(|qb| {
+            qb.where_expr(Expr::col("name").like(Expr::val("%box%")));
+            qb.or_where_expr(Expr::col("sku").eq(Expr::val("B-1")));
+        });
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT CAST(\"price\" AS INTEGER) AS whole FROM products WHERE \"name\" LIKE ? OR \"sku\" = ?"
+    );
+}
+
+#[test]
+fn test_expr_precedence_case_and_subquery() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("t")
+        .select_expr(
+            Expr::col("a")
+                .add(Expr::col("b"))
+                .mul(Expr::col("c"))
+                .sub(Expr::col("d").sub(Expr::col("e"))),
+            None,
+        )
+        .select_expr(
+            Expr::case(
+                vec![(Expr::col("score").gte(Expr::val(90)), Expr::val("A"))],
+                Some(Expr::val("B")),
+            ),
+            Some("grade"),
+        )
+        .query(|qb| {
+            qb.where_expr(
+                Expr::col("a")
+                    .eq(Expr::val(1))
+                    .or(Expr::col("b").eq(Expr::val(2)))
+                    .and(Expr::col("c").eq(Expr::val(3)))
+                    .not(),
+            );
+            qb.where_expr(Expr::col("limit").gt(Expr::subquery({
+                let mut sub = ChainBuilder::new(Client::Mysql);
+                sub.table("limits")
+                    .select_expr(Expr::func("MAX", vec![Expr::col("value")]), None);
+                sub
+            })));
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT (`a` + `b`) * `c` - (`d` - `e`), CASE WHEN `score` >= ? THEN ? ELSE ? END AS grade FROM t WHERE NOT ((`a` = ? OR `b` = ?) AND `c` = ?) AND `limit` > (SELECT MAX(`value`) FROM limits)"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from(90),
+            Value::from("A"),
+            Value::from("B"),
+            Value::from(1),
+            Value::from(2),
+            Value::from(3)
+        ]
+    );
+}
+
+#[test]
+fn test_expr_update_set_and_increment() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("products")
+        .update(serde_json::json!({"name": "Box"}))
+        .update_expr("price", Expr::col("price").mul(Expr::val(1.1)))
+        .query(|qb| qb.where_eq("id", Value::from(7)));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "UPDATE products SET name = ?, `price` = `price` * ? WHERE id = ?"
+    );
+    assert_eq!(
+        binds,
+        vec![Value::from("Box"), Value::from(1.1), Value::from(7)]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("posts")
+        .increment("views", 1)
+        .query(|qb| qb.where_eq("id", Value::from(3)));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "UPDATE posts SET \"views\" = \"views\" + ? WHERE id = ?"
+    );
+    assert_eq!(binds, vec![Value::from(1), Value::from(3)]);
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("stock").decrement("qty", 5);
+    assert_eq!(builder.to_sql().0, "UPDATE stock SET `qty` = `qty` - ?");
+}
+
+#[test]
+fn test_visitor_reaches_expr_subqueries() {
+    #[derive(Default)]
+    struct Tables(Vec<String>);
+
+    impl Visitor for Tables {
+        fn visit_chain_builder(&mut self, chain_builder: &ChainBuilder) {
+            if let Some(table) = chain_builder.table_name() {
+                self.0.push(table.to_string());
+            }
+            walk_chain_builder(self, chain_builder);
+        }
+    }
+
+    let mut sub = ChainBuilder::new(Client::Mysql);
+    sub.table("limits").select_raw("MAX(value)", None);
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("t").query(|qb| {
+        qb.order_by_expr(
+            Expr::func("COALESCE", vec![Expr::subquery(sub), Expr::val(0)]),
+            "ASC",
+        );
+    });
+
+    let mut tables = Tables::default();
+    tables.visit_chain_builder(&builder);
+    assert_eq!(tables.0, vec!["t".to_string(), "limits".to_string()]);
+}
+
+#[test]
+fn test_case_builder_in_select_and_order_by() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    let label = builder
+        .case()
+        .when(
+            |qb| {
+                qb.where_eq("status", Value::from("new"));
+                qb.where_gt("total", Value::from(100));
+            },
+            Value::from("big new"),
+        )
+        .when(
+            |qb| qb.where_eq("status", Value::from("new")),
+            Value::from("new"),
+        )
+        .else_(Expr::col("status"));
+    builder
+        .table("orders")
+        .select(Select::Columns(vec!["id".into()]))
+        .select_expr(label.end(), Some("label"))
+        .query(|qb| {
+            qb.where_eq("shop_id", Value::from(9));
+            let priority = qb
+                .case()
+                .when(
+                    |qb| qb.where_eq("status", Value::from("paid")),
+                    Value::from(1),
+                )
+                .else_(Value::from(2));
+            qb.order_by_expr(priority.end(), "ASC");
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT id, CASE WHEN status = ? AND total > ? THEN ? WHEN status = ? THEN ? ELSE `status` END AS label FROM orders WHERE shop_id = ? ORDER BY CASE WHEN status = ? THEN ? ELSE ? END ASC"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from("new"),
+            Value::from(100),
+            Value::from("big new"),
+            Value::from("new"),
+            Value::from("new"),
+            Value::from(9),
+            Value::from("paid"),
+            Value::from(1),
+            Value::from(2)
+        ]
+    );
+}
+
+#[test]
+fn test_case_builder_in_update_and_having() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    let tier = builder
+        .case()
+        .when(
+            |qb| qb.where_gte("points", Value::from(1000)),
+            Value::from("gold"),
+        )
+        .else_(Value::from("basic"));
+    builder
+        .table("members")
+        .update_expr("tier", tier.into())
+        .query(|qb| qb.where_eq("active", Value::from(true)));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "UPDATE members SET \"tier\" = CASE WHEN points >= ? THEN ? ELSE ? END WHERE active = ?"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from(1000),
+            Value::from("gold"),
+            Value::from("basic"),
+            Value::from(true)
+        ]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    let refunded = builder
+        .case()
+        .when(
+            |qb| {
+                qb.where_eq("type", Value::from("refund"));
+                qb.or().where_eq("type", Value::from("chargeback"));
+            },
+            Value::from(1),
+        )
+        .else_(Value::from(0));
+    builder
+        .table("payments")
+        .select(Select::Columns(vec!["user_id".into()]))
+        .query(|qb| {
+            qb.group_by(vec!["user_id".into()]);
+            qb.having_expr(Expr::func("SUM", vec![refunded.end()]).gt(Value::from(3).into()));
+        });
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT user_id FROM payments GROUP BY user_id HAVING SUM(CASE WHEN type = ? OR type = ? THEN ? ELSE ? END) > ?"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from("refund"),
+            Value::from("chargeback"),
+            Value::from(1),
+            Value::from(0),
+            Value::from(3)
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "at least one when()")]
+fn test_case_builder_requires_when() {
+    ChainBuilder::new(Client::Mysql)
+        .case()
+        .else_(Value::from(1))
+        .end();
+}
+
+#[test]
+fn test_aggregate_distinct_filter_and_alias() {
+    let paid = || Expr::col("status").eq(Value::from("paid").into());
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .select(Select::Columns(vec!["customer_id".into()]))
+        .select_aggregate(Aggregate::count_all(), Some("orders"))
+        .select_aggregate(Aggregate::count("product_id").distinct(), Some("products"))
+        .select_aggregate(Aggregate::count_all().filter(paid()), Some("paid_orders"))
+        .select_aggregate(Aggregate::sum("total").filter(paid()), Some("paid_total"))
+        .query(|qb| {
+            qb.group_by(vec!["customer_id".into()]);
+            qb.having_expr(Expr::from(Aggregate::avg("total")).gt(Value::from(10).into()));
+            qb.order_by_expr(Aggregate::max("created_at").into(), "DESC");
+        });
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT customer_id, COUNT(*) AS orders, COUNT(DISTINCT `product_id`) AS products, COUNT(CASE WHEN `status` = ? THEN 1 END) AS paid_orders, SUM(CASE WHEN `status` = ? THEN `total` END) AS paid_total FROM orders GROUP BY customer_id HAVING AVG(`total`) > ? ORDER BY MAX(`created_at`) DESC"
+    );
+    assert_eq!(
+        binds,
+        vec![Value::from("paid"), Value::from("paid"), Value::from(10)]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("orders")
+        .select_aggregate(Aggregate::count_all().filter(paid()), Some("paid_orders"))
+        .select_aggregate(
+            Aggregate::min("total").distinct().filter(paid()),
+            Some("smallest"),
+        );
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT COUNT(*) FILTER (WHERE \"status\" = ?) AS paid_orders, MIN(DISTINCT \"total\") FILTER (WHERE \"status\" = ?) AS smallest FROM orders"
+    );
+}
+
+#[test]
+fn test_aggregate_string_agg() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("tags")
+        .select_aggregate(
+            Aggregate::string_agg("name", ", ").distinct(),
+            Some("names"),
+        )
+        .select_aggregate(
+            Aggregate::string_agg("name", "|")
+                .filter(Expr::col("hidden").eq(Value::from(false).into())),
+            Some("visible"),
+        );
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT GROUP_CONCAT(DISTINCT `name` SEPARATOR ', ') AS names, GROUP_CONCAT(CASE WHEN `hidden` = ? THEN `name` END SEPARATOR '|') AS visible FROM tags"
+    );
+    assert_eq!(binds, vec![Value::from(false)]);
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("tags")
+        .select_aggregate(
+            Aggregate::string_agg("name", "|")
+                .filter(Expr::col("hidden").eq(Value::from(false).into())),
+            Some("visible"),
+        )
+        .select_aggregate(Aggregate::string_agg("name", ",").distinct(), None);
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT group_concat(\"name\", ?) FILTER (WHERE \"hidden\" = ?) AS visible, group_concat(DISTINCT \"name\") FROM tags"
+    );
+    assert_eq!(binds, vec![Value::from("|"), Value::from(false)]);
+}
+
+#[test]
+fn test_aggregate_string_agg_separator_is_escaped() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("tags")
+        .select_aggregate(Aggregate::string_agg("name", "'\\"), Some("names"));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT GROUP_CONCAT(`name` SEPARATOR '''\\\\') AS names FROM tags"
+    );
+    assert!(binds.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "only supports the \",\" separator")]
+fn test_aggregate_sqlite_distinct_separator() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("tags")
+        .select_aggregate(Aggregate::string_agg("name", "|").distinct(), None);
+    builder.to_sql();
+}
+
+#[test]
+fn test_group_by_rollup_mysql() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("sales")
+        .select(Select::Columns(vec!["region".into(), "product".into()]))
+        .select_expr(Expr::grouping("product"), Some("is_subtotal"))
+        .select_aggregate(Aggregate::sum("amount"), Some("total"))
+        .query(|qb| {
+            qb.where_eq("year", Value::from(2024));
+            qb.group_by_rollup(vec!["region".into(), "product".into()]);
+        });
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT region, product, GROUPING(`product`) AS is_subtotal, SUM(`amount`) AS total FROM sales WHERE year = ? GROUP BY region, product WITH ROLLUP"
+    );
+    assert_eq!(binds, vec![Value::from(2024)]);
+}
+
+#[test]
+fn test_group_by_rollup_sqlite_emulation() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("sales")
+        .select(Select::Columns(vec!["region".into(), "product".into()]))
+        .select_expr(Expr::grouping("product"), Some("is_subtotal"))
+        .select_aggregate(Aggregate::sum("amount"), Some("total"))
+        .query(|qb| {
+            qb.where_eq("year", Value::from(2024));
+            qb.group_by_rollup(vec!["region".into(), "product".into()]);
+            qb.order_by("region", "ASC");
+            qb.limit(10);
+        });
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM (SELECT region, product, 0 AS is_subtotal, SUM(\"amount\") AS total FROM sales WHERE year = ? GROUP BY region, product UNION ALL SELECT region, NULL AS product, 1 AS is_subtotal, SUM(\"amount\") AS total FROM sales WHERE year = ? GROUP BY region UNION ALL SELECT NULL AS region, NULL AS product, 1 AS is_subtotal, SUM(\"amount\") AS total FROM sales WHERE year = ?) ORDER BY region ASC LIMIT 10"
+    );
+    assert_eq!(
+        binds,
+        vec![Value::from(2024), Value::from(2024), Value::from(2024)]
+    );
+}
+
+#[test]
+fn test_group_by_rollup_sqlite_qualified_columns() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("sales")
+        .as_name("t")
+        .select(Select::Columns(vec!["t.region".into()]))
+        .select_expr(Expr::grouping("t.region"), Some("is_total"))
+        .select_aggregate(Aggregate::sum("t.amount"), Some("total"))
+        .query(|qb| qb.group_by_rollup(vec!["region".into()]));
+    let (sql, _) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM (SELECT t.region, 0 AS is_total, SUM(\"t\".\"amount\") AS total FROM sales AS t GROUP BY region UNION ALL SELECT NULL AS region, 1 AS is_total, SUM(\"t\".\"amount\") AS total FROM sales AS t)"
+    );
+}
+
+#[test]
+#[should_panic(expected = "region must be selected as a column to be rolled up on SQLite")]
+fn test_group_by_rollup_sqlite_unselected_column() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("sales")
+        .select_aggregate(Aggregate::sum("amount"), Some("total"))
+        .query(|qb| qb.group_by_rollup(vec!["region".into()]));
+    builder.to_sql();
+}
+
+#[test]
+#[should_panic(expected = "GROUPING() needs group_by_rollup on SQLite")]
+fn test_grouping_without_rollup_sqlite() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("sales")
+        .select_expr(Expr::grouping("region"), None);
+    builder.to_sql();
+}
+
+#[test]
+fn test_named_binds_in_raw_fragments() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table_raw_named(
+            "(SELECT * FROM events WHERE kind = :kind) AS e",
+            [("kind", Value::from("click"))],
+        )
+        .select_raw_named(
+            "COALESCE(e.score, :fallback) AS score",
+            [("fallback", Value::from(0))],
+        )
+        .query(|qb| {
+            qb.raw_join_named(
+                "LEFT JOIN users u ON u.id = e.user_id AND u.plan = :plan",
+                [("plan", Value::from("pro"))],
+            );
+            qb.where_raw_named(
+                "(e.created_at >= :from AND e.updated_at >= :from) OR e.owner = ':from'",
+                [("from", Value::from("2024-01-01"))],
+            );
+            qb.group_by(vec!["e.user_id".into()]);
+            qb.having_raw_named(
+                "COUNT(*) BETWEEN :min AND :max",
+                [("min", Value::from(2)), ("max", Value::from(9))],
+            );
+        });
+    builder.add_raw_named("LIMIT :n", [("n", Value::from(5))]);
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT COALESCE(e.score, ?) AS score FROM (SELECT * FROM events WHERE kind = ?) AS e LEFT JOIN users u ON u.id = e.user_id AND u.plan = ? WHERE (e.created_at >= ? AND e.updated_at >= ?) OR e.owner = ':from' GROUP BY e.user_id HAVING COUNT(*) BETWEEN ? AND ? LIMIT ?"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from(0),
+            Value::from("click"),
+            Value::from("pro"),
+            Value::from("2024-01-01"),
+            Value::from("2024-01-01"),
+            Value::from(2),
+            Value::from(9),
+            Value::from(5)
+        ]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    let mut named = std::collections::HashMap::new();
+    named.insert("id", Value::from(3));
+    builder.table("t").query(|qb| {
+        qb.where_eq("a", Value::from(1));
+        qb.or_where_raw_named("CAST(b AS TEXT)::text = :id OR c := 1 OR d = :id", named);
+    });
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "SELECT * FROM t WHERE a = ? OR CAST(b AS TEXT)::text = ? OR c := 1 OR d = ?"
+                .to_string(),
+            vec![Value::from(1), Value::from(3), Value::from(3)]
+        )
+    );
+}
+
+#[test]
+#[should_panic(expected = "no value for :missing")]
+fn test_named_binds_unknown_name() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("t").query(|qb| {
+        qb.where_raw_named("a = :a AND b = :missing", [("a", Value::from(1))]);
+    });
+    builder.to_sql();
+}
+
+#[test]
+#[should_panic(expected = ":extra is not used")]
+fn test_named_binds_unused_value() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("t").query(|qb| {
+        qb.where_raw_named("a = :a", [("a", Value::from(1)), ("extra", Value::from(2))]);
+    });
+    builder.to_sql();
+}
+
+#[test]
+fn test_named_binds_resolved_per_dialect() {
+    // a backslash escapes the quote on MySQL only, so `:x` is outside the string on SQLite
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("t").query(|qb| {
+        qb.where_raw_named(r"path = 'a\' OR x = :x", [("x", Value::from(1))]);
+    });
+    assert_eq!(
+        builder.to_sql_for(Client::Sqlite),
+        (
+            r"SELECT * FROM t WHERE path = 'a\' OR x = ?".to_string(),
+            vec![Value::from(1)]
+        )
+    );
+}
+
+#[test]
+fn test_named_binds_are_typed() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("t").query(|qb| {
+        qb.where_raw_named("a = :x", [("x", Value::from(1))]);
+        // an ordinary bind shaped like a JSON object stays a plain value
+        qb.where_raw(
+            "b = ?",
+            Some(vec![
+                serde_json::json!({ "$chain_builder_named": { "y": 2 } }),
+            ]),
+        );
+    });
+
+    match &builder.query_builder().statements()[0] {
+        Statement::RawNamed(sql, values) => {
+            assert_eq!(sql, "a = :x");
+            assert_eq!(values.get("x"), Some(&Value::from(1)));
+        }
+        statement => panic!("expected a named raw statement, got {:?}", statement),
+    }
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "SELECT * FROM t WHERE a = ? AND b = ?".to_string(),
+            vec![
+                Value::from(1),
+                serde_json::json!({ "$chain_builder_named": { "y": 2 } })
+            ]
+        )
+    );
+}
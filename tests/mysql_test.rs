@@ -1,5 +1,10 @@
-use chain_builder::{ChainBuilder, Client, JoinMethods, QueryCommon, Select, WhereClauses};
+use chain_builder::{
+    ChainBuilder, Client, CompiledQuery, JoinMethods, MissingColumn, Param, QueryBuilder,
+    QueryCommon, QueryTemplate, Select, SqlWriter, WhereClauses,
+};
 use serde_json::{self, Value};
+#[cfg(feature = "sqlx_mysql")]
+use sqlx::Arguments;
 use sqlx::Execute;
 
 #[test]
@@ -97,7 +102,7 @@ fn test_join() {
         Some(vec![Value::Number(1.into())]),
     ));
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {:?}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "SELECT *, (SELECT COUNT(*) FROM `mydb`.`users` WHERE users.id = ?) AS count FROM mydb.users JOIN mydb.details ON details.id = users.d_id AND details.id_w = users.d_id_w OR (details.id_s = users.d_id_s AND details.id_w = users.d_id_w) WHERE name = ?";
@@ -142,7 +147,7 @@ fn test_tow_join() {
         Some(vec![Value::Number(1.into())]),
     ));
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {:?}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "SELECT *, (SELECT COUNT(*) FROM `mydb`.`users` WHERE users.id = ?) AS count FROM mydb.users JOIN mydb.details ON details.id = users.d_id AND details.id_w = users.d_id_w OR (details.id_s = users.d_id_s AND details.id_w = users.d_id_w) JOIN mydb.address ON address.id = users.a_id AND address.id_w = users.a_id_w OR (address.id_s = users.a_id_s AND address.id_w = users.a_id_w) WHERE name = ?";
@@ -170,7 +175,7 @@ fn test_join_raw() {
         Some(vec![Value::Number(1.into())]),
     ));
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {:?}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "SELECT *, (SELECT COUNT(*) FROM `mydb`.`users` WHERE users.id = ?) AS count FROM mydb.users LEFT JOIN details ON details.id = users.d_id AND details.id_w = users.d_id_w OR (details.id_s = users.d_id_s AND details.id_w = users.d_id_w) WHERE name = ?";
@@ -194,7 +199,7 @@ fn test_insert() {
             "department": "IT",
         }));
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "INSERT INTO mydb.users (`city`, department, name) VALUES (?, ?, ?)";
@@ -229,7 +234,7 @@ fn test_insert_many() {
             }),
         ]);
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "INSERT INTO mydb.users (`city`, department, name) VALUES (?, ?, ?), (?, ?, ?)";
@@ -248,6 +253,117 @@ fn test_insert_many() {
     assert_eq!(to_sqlx.sql(), true_sql);
 }
 
+#[test]
+fn test_insert_many_missing_columns() {
+    let rows = vec![
+        serde_json::json!({ "name": "John", "department": "IT" }),
+        serde_json::json!({ "name": "Jane", "city": "Bangkok" }),
+    ];
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").insert_many(rows.clone());
+    let sql = builder.to_sql();
+    assert_eq!(
+        sql.0,
+        "INSERT INTO users (city, department, name) VALUES (?, ?, ?), (?, ?, ?)"
+    );
+    assert_eq!(
+        sql.1,
+        vec![
+            Value::Null,
+            Value::String("IT".to_string()),
+            Value::String("John".to_string()),
+            Value::String("Bangkok".to_string()),
+            Value::Null,
+            Value::String("Jane".to_string()),
+        ]
+    );
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .insert_many(rows)
+        .missing_column(MissingColumn::Default);
+    let sql = builder.to_sql();
+    assert_eq!(
+        sql.0,
+        "INSERT INTO users (city, department, name) VALUES (DEFAULT, ?, ?), (?, DEFAULT, ?)"
+    );
+    assert_eq!(sql.1.len(), 4);
+}
+
+#[test]
+#[should_panic(expected = "has no value for column \"department\"")]
+fn test_insert_many_missing_columns_error() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .insert_many(vec![
+            serde_json::json!({ "name": "John", "department": "IT" }),
+            serde_json::json!({ "name": "Jane" }),
+        ])
+        .missing_column(MissingColumn::Error);
+    builder.to_sql();
+}
+
+#[test]
+fn test_insert_ignore_and_upsert() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .insert_ignore(serde_json::json!({ "id": 1, "name": "John" }));
+    let sql = builder.to_sql();
+    assert_eq!(sql.0, "INSERT IGNORE INTO users (id, name) VALUES (?, ?)");
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").insert_or_update(
+        serde_json::json!({ "id": 1, "name": "John" }),
+        serde_json::json!({ "name": "John" }),
+    );
+    let sql = builder.to_sql();
+    assert_eq!(
+        sql.0,
+        "INSERT INTO users (id, name) VALUES (?, ?) ON DUPLICATE KEY UPDATE name = ?"
+    );
+    assert_eq!(
+        sql.1,
+        vec![
+            Value::Number(1.into()),
+            Value::String("John".to_string()),
+            Value::String("John".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_insert_from() {
+    let mut source = ChainBuilder::new(Client::Mysql);
+    source
+        .db("mydb")
+        .table("users")
+        .select(Select::Columns(vec!["id".into(), "name".into()]))
+        .query(|qb| {
+            qb.where_eq("status", Value::String("archived".to_string()));
+        });
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .db("mydb")
+        .table("archived_users")
+        .insert_from(vec!["id".into(), "name".into()], source)
+        .ignore()
+        .upsert(serde_json::json!({ "archived": true }));
+    let sql = builder.to_sql();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
+    let true_sql = "INSERT IGNORE INTO mydb.archived_users (id, name) SELECT id, name FROM mydb.users WHERE status = ? ON DUPLICATE KEY UPDATE archived = ?";
+    assert_eq!(sql.0, true_sql);
+    assert_eq!(
+        sql.1,
+        vec![Value::String("archived".to_string()), Value::Bool(true)]
+    );
+    assert_eq!(to_sqlx.sql(), true_sql);
+}
+
 #[test]
 fn test_update() {
     let mut builder = ChainBuilder::new(Client::Mysql);
@@ -263,7 +379,7 @@ fn test_update() {
             qb.where_eq("id", Value::Number(1.into()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "UPDATE mydb.users SET `city` = ?, department = ?, name = ? WHERE id = ?";
@@ -291,7 +407,7 @@ fn test_delete() {
             qb.where_eq("id", Value::Number(1.into()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "DELETE FROM mydb.users WHERE id = ?";
@@ -329,7 +445,7 @@ fn test_with() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "WITH active_users AS (SELECT *, (SELECT * FROM mydb.address WHERE city = ?) AS address FROM mydb.users WHERE status = ?) SELECT * FROM active_users WHERE name = ?";
@@ -374,7 +490,7 @@ fn test_with_recursive() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     // println!("final sql: {}", sql.0);
     // println!("final binds: {:?}", sql.1);
     let true_sql = "WITH RECURSIVE active_users AS (SELECT *, (SELECT * FROM mydb.address WHERE city = ?) AS address FROM mydb.users WHERE status = ?) SELECT * FROM active_users WHERE name = ?";
@@ -411,7 +527,7 @@ fn test_union() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql =
         "SELECT * FROM mydb.users WHERE name = ? UNION SELECT * FROM mydb.users WHERE status = ?";
     assert_eq!(sql.0, true_sql);
@@ -447,7 +563,7 @@ fn test_union_all() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql =
         "SELECT * FROM mydb.users WHERE name = ? UNION ALL SELECT * FROM mydb.users WHERE status = ? UNION ALL SELECT * FROM mydb.users WHERE status = ?";
     assert_eq!(sql.0, true_sql);
@@ -475,7 +591,7 @@ fn test_limit_offset() {
             qb.offset(5);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql = "SELECT * FROM mydb.users WHERE name = ? LIMIT ? OFFSET ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -503,7 +619,7 @@ fn test_group_by() {
             qb.group_by(vec!["name".to_string(), "city".to_string()]);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql = "SELECT * FROM mydb.users WHERE name = ? GROUP BY name, city LIMIT ? OFFSET ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -531,7 +647,7 @@ fn test_group_by_raw() {
             qb.group_by_raw("name, city", None);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql = "SELECT * FROM mydb.users WHERE name = ? GROUP BY name, city LIMIT ? OFFSET ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -560,7 +676,7 @@ fn test_order_by() {
             qb.order_by("city", "DESC");
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql =
         "SELECT * FROM mydb.users WHERE name = ? ORDER BY name ASC, city DESC LIMIT ? OFFSET ?";
     assert_eq!(sql.0, true_sql);
@@ -589,7 +705,7 @@ fn test_order_by_raw() {
             qb.order_by_raw("`count`, `name` order by (`name` is not null) desc", None);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql = "SELECT * FROM mydb.users WHERE name = ? ORDER BY `count`, `name` order by (`name` is not null) desc LIMIT ? OFFSET ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -622,7 +738,7 @@ fn test_table_raw() {
             qb.order_by_raw("`count`, `name` order by (`name` is not null) desc", None);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::MySql>();
     let true_sql = "SELECT * FROM (SELECT * FROM users WHERE id = ?) as pp WHERE name = ? AND count > ? ORDER BY `count`, `name` order by (`name` is not null) desc LIMIT ? OFFSET ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -637,3 +753,363 @@ fn test_table_raw() {
     );
     assert_eq!(to_sqlx.sql(), true_sql);
 }
+
+#[test]
+fn test_query_template_rebind() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<QueryTemplate>();
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .select(Select::Columns(vec!["id".into()]))
+        .query(|qb| {
+            qb.where_eq("user_id", Param("user_id").into());
+            qb.where_in("status", vec![Param("status").into(), Value::from("open")]);
+            qb.where_gte("total", Param("min_total").into());
+            qb.or_where_eq("owner_id", Param("user_id").into());
+            qb.limit(20);
+        });
+    let template = builder.to_template();
+    let true_sql = "SELECT id FROM orders WHERE user_id = ? AND status IN (?,?) AND total >= ? OR owner_id = ? LIMIT ?";
+    assert_eq!(template.sql(), true_sql);
+    assert_eq!(template.params(), vec!["user_id", "status", "min_total"]);
+
+    for user_id in [1, 2] {
+        let (sql, binds) = template.bind([
+            ("min_total", Value::from(10)),
+            ("user_id", Value::from(user_id)),
+            ("status", Value::from("paid")),
+        ]);
+        assert_eq!(sql, true_sql);
+        assert_eq!(
+            binds,
+            vec![
+                Value::from(user_id),
+                Value::from("paid"),
+                Value::from("open"),
+                Value::from(10),
+                Value::from(user_id),
+                Value::from(20)
+            ]
+        );
+    }
+
+    #[cfg(feature = "sqlx_mysql")]
+    {
+        let to_sqlx = template.to_sqlx_query::<sqlx::MySql>([
+            ("user_id", Value::from(1)),
+            ("status", Value::from("paid")),
+            ("min_total", Value::from(0)),
+        ]);
+        assert_eq!(to_sqlx.sql(), true_sql);
+    }
+}
+
+#[test]
+#[should_panic(expected = "no value for slot min_total")]
+fn test_query_template_missing_value() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .query(|qb| qb.where_gte("total", Param("min_total").into()));
+    builder.to_template().bind([]);
+}
+
+#[test]
+#[should_panic(expected = "no slot named typo")]
+fn test_query_template_unknown_param() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .query(|qb| qb.where_gte("total", Param("min_total").into()));
+    builder
+        .to_template()
+        .bind([("min_total", Value::from(1)), ("typo", Value::from(2))]);
+}
+
+#[test]
+#[should_panic(expected = "Param(\"min_total\") can only be compiled with to_template()")]
+fn test_param_outside_template() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("orders")
+        .query(|qb| qb.where_gte("total", Param("min_total").into()));
+    // a template compiles from a shared borrow
+    let shared = &builder;
+    assert_eq!(shared.to_template().params(), vec!["min_total"]);
+    builder.build();
+}
+
+#[test]
+fn test_build_compiled_query() {
+    fn assert_owned<T: Clone + Send + 'static>() {}
+    assert_owned::<CompiledQuery>();
+
+    let query = {
+        let mut builder = ChainBuilder::new(Client::Mysql);
+        builder
+            .table("users")
+            .soft_delete("deleted_at")
+            .query(|qb| qb.where_eq("id", Value::from(1)));
+        let shared = &builder;
+        let query = shared.build();
+        // build() compiles from a shared borrow and matches to_sql()
+        assert_eq!((query.sql.clone(), query.binds.clone()), builder.to_sql());
+        query
+    };
+
+    let true_sql = "SELECT * FROM users WHERE id = ? AND users.deleted_at IS NULL";
+    let handle = std::thread::spawn(move || {
+        #[cfg(feature = "sqlx_mysql")]
+        assert_eq!(query.to_sqlx_query::<sqlx::MySql>().sql(), true_sql);
+        query
+    });
+    let query = handle.join().unwrap();
+    assert!(matches!(query.dialect, Client::Mysql));
+    assert_eq!(query.sql, true_sql);
+    assert_eq!(query.binds, vec![Value::from(1)]);
+}
+
+#[cfg(feature = "sqlx_mysql")]
+#[test]
+#[should_panic(expected = "built for Sqlite, executed on MySQL")]
+fn test_compiled_query_dialect_mismatch() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("users");
+    let query = builder.build();
+    let _ = query.to_sqlx_query::<sqlx::MySql>();
+}
+
+#[test]
+fn test_deeply_nested_filters() {
+    fn nest(qb: &mut QueryBuilder, depth: i64) {
+        qb.where_eq("a", Value::from(depth));
+        if depth > 0 {
+            qb.or().where_subquery(|sub| {
+                sub.where_eq("b", Value::from(depth));
+                nest(sub, depth - 1);
+            });
+        }
+    }
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| nest(qb, 2));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE a = ? OR (b = ? AND a = ? OR (b = ? AND a = ?))"
+    );
+    assert_eq!(binds, [2, 2, 1, 1, 0].map(Value::from).to_vec());
+}
+
+#[test]
+#[cfg(feature = "sqlx_mysql")]
+fn test_write_sql_to_sqlx_query_builder() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| {
+        qb.where_in("id", vec![Value::from(1), Value::from(2)]);
+        qb.where_raw("note <> 'why?' AND age > ?", Some(vec![Value::from(18)]));
+        qb.limit(10);
+    });
+
+    let mut query_builder = sqlx::QueryBuilder::<sqlx::MySql>::new("");
+    builder.write_sql(&mut query_builder);
+    assert_eq!(
+        query_builder.sql(),
+        "SELECT * FROM users WHERE id IN (?,?) AND note <> 'why?' AND age > ? LIMIT ?"
+    );
+}
+
+#[test]
+fn test_write_sql_custom_writer() {
+    // counts binds instead of storing them
+    #[derive(Default)]
+    struct Counter {
+        sql: String,
+        binds: usize,
+    }
+
+    impl SqlWriter for Counter {
+        fn push_sql(&mut self, sql: &str) {
+            self.sql.push_str(sql);
+        }
+
+        fn push_bind(&mut self, _value: Value) {
+            self.binds += 1;
+            self.sql.push('?');
+        }
+    }
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select_raw("COUNT(*) > ? AS many", Some(vec![Value::from(5)]))
+        .query(|qb| qb.where_eq("status", Value::from("active")));
+    let mut counter = Counter::default();
+    builder.write_sql(&mut counter);
+    assert_eq!(counter.binds, 2);
+    assert_eq!(counter.sql, builder.to_sql().0);
+}
+
+#[test]
+#[should_panic(expected = "[Err] raw: 2 placeholders for 1 binds")]
+fn test_to_sql_raw_bind_count_mismatch() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| {
+        qb.where_raw("age BETWEEN ? AND ?", Some(vec![Value::from(18)]));
+    });
+    builder.to_sql();
+}
+
+#[test]
+#[should_panic(expected = "[Err] raw: 0 placeholders for 1 binds")]
+fn test_write_sql_raw_bind_count_mismatch() {
+    // `?` inside a string literal is not a placeholder
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| {
+        qb.where_raw("note <> 'why?'", Some(vec![Value::from(1)]));
+    });
+    let mut writer = (String::new(), vec![]);
+    builder.write_sql(&mut writer);
+}
+
+#[test]
+fn test_with_multiple_ctes() {
+    let mut admins = ChainBuilder::new(Client::Mysql);
+    admins
+        .table("users")
+        .query(|qb| qb.where_eq("role", Value::from("admin")));
+    let mut tree = ChainBuilder::new(Client::Mysql);
+    tree.table("categories");
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .with("admins", admins)
+        .with_recursive("tree", tree)
+        .table("admins");
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "WITH RECURSIVE admins AS (SELECT * FROM users WHERE role = ?), tree AS (SELECT * FROM categories) SELECT * FROM admins"
+    );
+    assert_eq!(binds, vec![Value::from("admin")]);
+}
+
+#[test]
+#[cfg(feature = "sqlx_mysql")]
+fn test_push_to_sqlx() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("orders").query(|qb| {
+        qb.where_eq("status", Value::from("paid"));
+        qb.where_eq("external_id", Value::from(u64::MAX));
+    });
+
+    let mut query_builder = sqlx::QueryBuilder::<sqlx::MySql>::new("SELECT COUNT(*) FROM (");
+    builder.push_to_sqlx(&mut query_builder);
+    query_builder.push(") o WHERE o.user_id = ");
+    query_builder.push_bind(7u32);
+    assert_eq!(
+        query_builder.sql(),
+        "SELECT COUNT(*) FROM (SELECT * FROM orders WHERE status = ? AND external_id = ?) o WHERE o.user_id = ?"
+    );
+    // u64::MAX is bound as BIGINT UNSIGNED, not as a string or a wrapped i64
+    let mut expected = sqlx::mysql::MySqlArguments::default();
+    Arguments::add(&mut expected, "paid".to_string()).unwrap();
+    Arguments::add(&mut expected, u64::MAX).unwrap();
+    Arguments::add(&mut expected, 7u32).unwrap();
+    let arguments = query_builder.build().take_arguments().unwrap().unwrap();
+    assert_eq!(format!("{:?}", arguments), format!("{:?}", expected));
+}
+
+#[test]
+#[cfg(feature = "sqlx_mysql")]
+fn test_push_to_sqlx_separated() {
+    let mut query_builder =
+        sqlx::QueryBuilder::<sqlx::MySql>::new("INSERT INTO user_totals (user_id, total) ");
+    query_builder.push_values([1, 2], |mut row, user_id| {
+        let mut total = ChainBuilder::new(Client::Mysql);
+        total
+            .table("orders")
+            .select_raw("SUM(amount)", None)
+            .query(|qb| qb.where_eq("user_id", Value::from(user_id)));
+        row.push_bind(user_id);
+        total.push_to_sqlx_separated(&mut row);
+    });
+    assert_eq!(
+        query_builder.sql(),
+        "INSERT INTO user_totals (user_id, total) VALUES (?, (SELECT SUM(amount) FROM orders WHERE user_id = ?)), (?, (SELECT SUM(amount) FROM orders WHERE user_id = ?))"
+    );
+}
+
+#[test]
+fn test_in_list_threshold() {
+    let ids: Vec<Value> = (1..=5).map(Value::from).collect();
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .select(Select::Columns(vec!["*".into()]))
+        .in_list_threshold(3)
+        .query(|qb| {
+            qb.where_in("id", ids.clone());
+            qb.where_not_in("status", vec![Value::from("banned"), Value::from("gone")]);
+            qb.where_not_in(
+                "name",
+                vec![
+                    Value::from("a"),
+                    Value::from("bb"),
+                    Value::from("cccc"),
+                    Value::from("d"),
+                ],
+            );
+            qb.where_exists(|sub| {
+                sub.table("orders")
+                    .select_raw("1", None)
+                    .query(|qb| qb.where_in("orders.id", ids.clone()));
+            });
+        });
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE id IN (SELECT value FROM JSON_TABLE(?, '$[*]' COLUMNS (value BIGINT PATH '$')) AS in_list) AND status NOT IN (?,?) AND name NOT IN (?,?,?,?) AND EXISTS (SELECT 1 FROM orders WHERE orders.id IN (SELECT value FROM JSON_TABLE(?, '$[*]' COLUMNS (value BIGINT PATH '$')) AS in_list))"
+    );
+    // string lists stay inline: JSON_TABLE strings are utf8mb4_bin
+    assert_eq!(binds.len(), 8);
+    assert_eq!(binds[0], Value::String("[1,2,3,4,5]".to_string()));
+    assert_eq!(binds[3], Value::from("a"));
+    assert_eq!(binds[7], Value::String("[1,2,3,4,5]".to_string()));
+
+    // unsigned integers above i64::MAX get a BIGINT UNSIGNED column
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("orders").in_list_threshold(1).query(|qb| {
+        qb.where_in("external_id", vec![Value::from(1), Value::from(u64::MAX)]);
+    });
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT * FROM orders WHERE external_id IN (SELECT value FROM JSON_TABLE(?, '$[*]' COLUMNS (value BIGINT UNSIGNED PATH '$')) AS in_list)"
+    );
+
+    // mixed lists have no JSON_TABLE column type and stay inline
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").in_list_threshold(1).query(|qb| {
+        qb.where_in("tag", vec![Value::from(1), Value::from("one")]);
+    });
+    assert_eq!(builder.to_sql().0, "SELECT * FROM users WHERE tag IN (?,?)");
+}
+
+#[test]
+#[should_panic(expected = "insert_many: no rows to insert")]
+fn test_insert_many_no_rows() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").insert_many(vec![]);
+}
+
+#[test]
+fn test_insert_many_empty_rows() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .insert_many(vec![serde_json::json!({}), serde_json::json!({})]);
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(sql, "INSERT INTO users () VALUES (), ()");
+    assert!(binds.is_empty());
+}
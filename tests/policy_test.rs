@@ -0,0 +1,349 @@
+use chain_builder::{
+    ChainBuilder, Client, Expr, HavingClauses, JoinMethods, QueryCommon, Select, WhereClauses,
+};
+use serde_json::Value;
+
+#[test]
+fn test_soft_delete_select() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .soft_delete("deleted_at")
+        .select(Select::Columns(vec!["*".into()]))
+        .query(|qb| {
+            qb.left_join("profiles", |join| {
+                join.on("users.id", "=", "profiles.user_id");
+            });
+            qb.where_eq("status", Value::String("active".to_string()));
+            qb.or().where_eq("role", Value::String("admin".to_string()));
+            qb.where_exists(|sub| {
+                sub.table("orders");
+            });
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM users LEFT JOIN profiles ON users.id = profiles.user_id AND profiles.deleted_at IS NULL WHERE (status = ? OR role = ? AND EXISTS (SELECT * FROM orders WHERE orders.deleted_at IS NULL)) AND users.deleted_at IS NULL"
+    );
+    assert_eq!(binds.len(), 2);
+    // the builder itself is not rewritten
+    assert_eq!(builder.query_builder().statements().len(), 3);
+}
+
+#[test]
+fn test_soft_delete_trashed() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .as_name("u")
+        .soft_delete("deleted_at")
+        .only_trashed()
+        .select(Select::Columns(vec!["*".into()]));
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT * FROM users AS u WHERE u.deleted_at IS NOT NULL"
+    );
+
+    builder.with_trashed();
+    assert_eq!(builder.to_sql().0, "SELECT * FROM users AS u");
+}
+
+#[test]
+fn test_soft_delete_delete() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .soft_delete("deleted_at")
+        .delete()
+        .query(|qb| {
+            qb.where_eq("id", Value::Number(1.into()));
+        });
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "UPDATE users SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND users.deleted_at IS NULL"
+                .to_string(),
+            vec![Value::Number(1.into())]
+        )
+    );
+
+    builder.force_delete();
+    assert_eq!(
+        builder.to_sql().0,
+        "DELETE FROM users WHERE id = ? AND users.deleted_at IS NULL"
+    );
+
+    builder.only_trashed();
+    assert_eq!(
+        builder.to_sql().0,
+        "DELETE FROM users WHERE id = ? AND users.deleted_at IS NOT NULL"
+    );
+}
+
+#[test]
+fn test_soft_delete_update_sqlite() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("users")
+        .soft_delete("deleted_at")
+        .update(serde_json::json!({ "name": "John" }))
+        .update_raw("version = version + ?", Some(vec![Value::Number(1.into())]))
+        .query(|qb| {
+            qb.where_eq("id", Value::Number(1.into()));
+        });
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "UPDATE users SET name = ?, version = version + ? WHERE id = ? AND users.deleted_at IS NULL"
+                .to_string(),
+            vec![
+                Value::String("John".to_string()),
+                Value::Number(1.into()),
+                Value::Number(1.into())
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_soft_delete_alias_update() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("users")
+        .as_name("u")
+        .soft_delete("deleted_at")
+        .delete()
+        .query(|qb| {
+            qb.where_eq("id", Value::Number(1.into()));
+        });
+    assert_eq!(
+        builder.to_sql().0,
+        "UPDATE users SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND users.deleted_at IS NULL"
+    );
+
+    builder.update(serde_json::json!({ "name": "John" }));
+    assert_eq!(
+        builder.to_sql().0,
+        "UPDATE users SET name = ? WHERE id = ? AND users.deleted_at IS NULL"
+    );
+}
+
+#[test]
+#[should_panic(expected = "only_trashed().delete() would re-stamp deleted rows")]
+fn test_soft_delete_only_trashed_delete() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .table("users")
+        .soft_delete("deleted_at")
+        .only_trashed()
+        .delete();
+    builder.to_sql();
+}
+
+#[test]
+fn test_tenant_select_tree() {
+    let mut recent = ChainBuilder::new(Client::Mysql);
+    recent.table("orders").query(|qb| {
+        qb.where_gt("total", Value::Number(100.into()));
+    });
+    let mut archived = ChainBuilder::new(Client::Mysql);
+    archived.table("archived_users");
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::Number(7.into()))
+        .with("recent", recent)
+        .table("users")
+        .select(Select::Columns(vec!["*".into()]))
+        .union(archived)
+        .query(|qb| {
+            qb.join("recent", |join| {
+                join.on("recent.user_id", "=", "users.id");
+            });
+            qb.left_join("profiles", |join| {
+                join.on("profiles.user_id", "=", "users.id");
+            });
+            qb.where_eq("name", Value::from("x"));
+            qb.or_where_eq("is_admin", Value::Bool(true));
+        });
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "WITH recent AS (SELECT * FROM orders WHERE total > ? AND orders.tenant_id = ?) SELECT * FROM users JOIN recent ON recent.user_id = users.id LEFT JOIN profiles ON profiles.user_id = users.id AND profiles.tenant_id = ? WHERE (name = ? OR is_admin = ?) AND users.tenant_id = ? UNION SELECT * FROM archived_users WHERE archived_users.tenant_id = ?"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from(100),
+            Value::from(7),
+            Value::from(7),
+            Value::from("x"),
+            Value::Bool(true),
+            Value::from(7),
+            Value::from(7)
+        ]
+    );
+}
+
+#[test]
+fn test_tenant_insert() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("users")
+        .insert_many(vec![
+            serde_json::json!({ "name": "a" }),
+            serde_json::json!({ "name": "b", "tenant_id": 7 }),
+        ]);
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "INSERT INTO users (name, tenant_id) VALUES (?, ?), (?, ?)".to_string(),
+            vec![
+                Value::from("a"),
+                Value::from(7),
+                Value::from("b"),
+                Value::from(7)
+            ]
+        )
+    );
+
+    let mut source = ChainBuilder::new(Client::Sqlite);
+    source
+        .table("staged_users")
+        .select(Select::Columns(vec!["name".into()]));
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("users")
+        .insert_from(vec!["name".into()], source);
+    assert_eq!(
+        builder.to_sql().0,
+        "INSERT INTO users (name, tenant_id) SELECT name, ? FROM staged_users WHERE staged_users.tenant_id = ?"
+    );
+
+    // SELECT * source
+    let mut source = ChainBuilder::new(Client::Mysql);
+    source.table("staged_users");
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("users")
+        .insert_from(vec!["id".into(), "name".into()], source);
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT INTO users (id, name, tenant_id) SELECT *, ? FROM staged_users WHERE staged_users.tenant_id = ?"
+    );
+    assert_eq!(binds, vec![Value::from(7), Value::from(7)]);
+}
+
+#[test]
+#[should_panic(expected = "insert row belongs to another tenant")]
+fn test_tenant_insert_other_tenant() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("users")
+        .insert(serde_json::json!({ "name": "a", "tenant_id": 8 }));
+    builder.to_sql();
+}
+
+#[test]
+#[should_panic(expected = "raw joins would bypass the tenant policy")]
+fn test_tenant_raw_join() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("users")
+        .query(|qb| {
+            qb.raw_join("JOIN roles USING (role_id)", None);
+        });
+    builder.to_sql();
+}
+
+#[test]
+fn test_tenant_join_using() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("users")
+        .query(|qb| {
+            qb.join_using("roles", vec!["role_id".into()]);
+            qb.where_eq("roles.name", Value::from("admin"));
+        });
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "SELECT * FROM users JOIN roles USING (role_id) WHERE roles.name = ? AND users.tenant_id = ? AND roles.tenant_id = ?".to_string(),
+            vec![Value::from("admin"), Value::from(7), Value::from(7)]
+        )
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "[Err] tenant: raw select, where or having SQL would bypass the tenant policy"
+)]
+fn test_tenant_select_raw() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("t")
+        .select_raw("(SELECT COUNT(*) FROM secrets) AS n", None);
+    builder.to_sql();
+}
+
+#[test]
+#[should_panic(
+    expected = "[Err] tenant: raw select, where or having SQL would bypass the tenant policy"
+)]
+fn test_tenant_where_raw_in_group() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("t")
+        .query(|qb| {
+            qb.where_eq("a", Value::from(1));
+            qb.or_where_raw("id IN (SELECT id FROM other)", None);
+        });
+    builder.to_sql();
+}
+
+#[test]
+#[should_panic(expected = "[Err] tenant: Expr::raw would bypass the tenant policy")]
+fn test_tenant_expr_raw_in_subquery() {
+    // nested builders inherit the policy, so their raw fragments are rejected too
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("t")
+        .query(|qb| {
+            qb.where_exists(|sub| {
+                sub.table("u")
+                    .query(|qb| qb.where_expr(Expr::raw("u.id = t.uid", None)));
+            });
+        });
+    builder.to_sql();
+}
+
+#[test]
+fn test_tenant_allows_generated_fragments() {
+    // helpers that build their own SQL from column names are not raw fragments
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .tenant("tenant_id", Value::from(7))
+        .table("t")
+        .select_count("id")
+        .query(|qb| {
+            qb.where_column("t.a", "=", "t.b");
+            qb.group_by(vec!["t.c".into()]);
+            qb.having("COUNT(id)", ">", Value::from(1));
+        });
+    assert_eq!(
+        builder.to_sql().0,
+        "SELECT COUNT(id) FROM t WHERE (t.a = t.b) AND t.tenant_id = ? GROUP BY t.c HAVING COUNT(id) > ?"
+    );
+}
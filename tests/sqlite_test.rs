@@ -1,5 +1,6 @@
 use chain_builder::{
-    ChainBuilder, Client, HavingClauses, JoinMethods, QueryCommon, Select, WhereClauses,
+    ChainBuilder, Client, Expr, HavingClauses, JoinMethods, MissingColumn, NullsOrder, Param,
+    QueryBuilder, QueryCommon, QueryPlan, Select, WhereClauses,
 };
 use serde_json::Value;
 use sqlx::Execute;
@@ -163,7 +164,7 @@ fn test_sqlite_join() {
         Some(vec![Value::Number(1.into())]),
     ));
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT *, (SELECT COUNT(*) FROM `mydb`.`users` WHERE users.id = ?) AS count FROM mydb.users JOIN mydb.details ON details.id = users.d_id AND details.id_w = users.d_id_w OR (details.id_s = users.d_id_s AND details.id_w = users.d_id_w) WHERE name = ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -190,7 +191,7 @@ fn test_sqlite_tow_join() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users JOIN mydb.details ON details.id = users.d_id JOIN mydb.profiles ON profiles.id = users.p_id WHERE name = ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(sql.1, vec![Value::String("John".to_string())]);
@@ -212,7 +213,7 @@ fn test_sqlite_join_raw() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users JOIN details ON details.id = users.d_id AND details.status = ? WHERE name = ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
@@ -268,6 +269,108 @@ fn test_sqlite_insert_many() {
     assert_eq!(binds.len(), 6);
 }
 
+#[test]
+fn test_sqlite_insert_many_missing_columns() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("users").insert_many(vec![
+        serde_json::json!({ "name": "John Doe", "age": 30 }),
+        serde_json::json!({ "name": "Jane Smith", "email": "jane@example.com" }),
+    ]);
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT INTO users (age, email, name) VALUES (?, ?, ?), (?, ?, ?)"
+    );
+    assert_eq!(binds[1], Value::Null);
+    assert_eq!(binds[3], Value::Null);
+}
+
+#[test]
+fn test_sqlite_insert_many_missing_columns_default() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("users")
+        .insert_many(vec![
+            serde_json::json!({ "name": "John Doe", "age": 30 }),
+            serde_json::json!({ "name": "Jim Doe", "age": 31 }),
+            serde_json::json!({ "name": "Jane Smith", "email": "jane@example.com" }),
+        ])
+        .missing_column(MissingColumn::Default);
+
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT INTO users (age, name) VALUES (?, ?), (?, ?); INSERT INTO users (email, name) VALUES (?, ?)"
+    );
+    assert_eq!(binds.len(), 6);
+}
+
+#[test]
+fn test_sqlite_insert_from() {
+    let mut source = ChainBuilder::new(Client::Sqlite);
+    source
+        .table("users")
+        .select(Select::Columns(vec!["id".into(), "name".into()]));
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("archived_users")
+        .insert_from(vec!["id".into(), "name".into()], source.clone())
+        .ignore();
+    let (sql, _) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT OR IGNORE INTO archived_users (id, name) SELECT id, name FROM users"
+    );
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("archived_users")
+        .insert_from(vec!["id".into(), "name".into()], source)
+        .upsert(serde_json::json!({ "name": "unknown" }));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT INTO archived_users (id, name) SELECT * FROM (SELECT id, name FROM users) WHERE true ON CONFLICT DO UPDATE SET name = ?"
+    );
+    assert_eq!(binds, vec![Value::String("unknown".to_string())]);
+}
+
+#[test]
+fn test_sqlite_debug_sql() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("users").insert(serde_json::json!({
+        "name": "it's \\ fine",
+        "active": false,
+        "tags": ["a", "b"],
+    }));
+    assert_eq!(
+        builder.to_debug_sql(),
+        "INSERT INTO users (active, name, tags) VALUES (0, 'it''s \\ fine', '[\"a\",\"b\"]')"
+    );
+}
+
+#[test]
+fn test_sqlite_query_plan() {
+    let plan = QueryPlan::from_sqlite([
+        "SEARCH users USING INDEX idx_email (email=?)",
+        "SCAN TABLE orders",
+        "SEARCH items USING INTEGER PRIMARY KEY (rowid=?)",
+        "USE TEMP B-TREE FOR ORDER BY",
+    ]);
+    assert_eq!(plan.nodes.len(), 3);
+    assert_eq!(plan.node("users").unwrap().access_type, "SEARCH");
+    assert_eq!(
+        plan.node("users").unwrap().index.as_deref(),
+        Some("idx_email")
+    );
+    assert!(plan.uses_index("users"));
+    assert!(plan.uses_index("items"));
+    assert!(!plan.uses_index("orders"));
+    assert!(plan.has_full_scan());
+}
+
 #[test]
 fn test_sqlite_update() {
     let mut builder = ChainBuilder::new(Client::Sqlite);
@@ -337,13 +440,14 @@ fn test_sqlite_with() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
-    let true_sql = "WITH active_users AS (SELECT *, (SELECT * FROM mydb.address WHERE city = ?) AS address FROM mydb.users)SELECT * FROM active_users WHERE name = ?";
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
+    let true_sql = "WITH active_users AS (SELECT *, (SELECT * FROM mydb.address WHERE city = ?) AS address FROM mydb.users WHERE status = ?) SELECT * FROM active_users WHERE name = ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
         sql.1,
         vec![
             Value::String("New York".to_string()),
+            Value::String("active".to_string()),
             Value::String("John".to_string())
         ]
     );
@@ -379,13 +483,14 @@ fn test_sqlite_with_recursive() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
-    let true_sql = "WITH RECURSIVE active_users AS (SELECT *, (SELECT * FROM mydb.address WHERE city = ?) AS address FROM mydb.users)SELECT * FROM active_users WHERE name = ?";
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
+    let true_sql = "WITH RECURSIVE active_users AS (SELECT *, (SELECT * FROM mydb.address WHERE city = ?) AS address FROM mydb.users WHERE status = ?) SELECT * FROM active_users WHERE name = ?";
     assert_eq!(sql.0, true_sql);
     assert_eq!(
         sql.1,
         vec![
             Value::String("New York".to_string()),
+            Value::String("active".to_string()),
             Value::String("John".to_string())
         ]
     );
@@ -413,10 +518,17 @@ fn test_sqlite_union() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
-    let true_sql = "SELECT * FROM mydb.users WHERE name = ? UNION SELECT * FROM mydb.users";
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
+    let true_sql =
+        "SELECT * FROM mydb.users WHERE name = ? UNION SELECT * FROM mydb.users WHERE status = ?";
     assert_eq!(sql.0, true_sql);
-    assert_eq!(sql.1, vec![Value::String("John".to_string()),]);
+    assert_eq!(
+        sql.1,
+        vec![
+            Value::String("John".to_string()),
+            Value::String("pending".to_string())
+        ]
+    );
     assert_eq!(to_sqlx.sql(), true_sql);
 }
 
@@ -441,10 +553,17 @@ fn test_sqlite_union_all() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
-    let true_sql = "SELECT * FROM mydb.users WHERE name = ? UNION ALL SELECT * FROM mydb.users";
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
+    let true_sql =
+        "SELECT * FROM mydb.users WHERE name = ? UNION ALL SELECT * FROM mydb.users WHERE status = ?";
     assert_eq!(sql.0, true_sql);
-    assert_eq!(sql.1, vec![Value::String("John".to_string()),]);
+    assert_eq!(
+        sql.1,
+        vec![
+            Value::String("John".to_string()),
+            Value::String("pending".to_string())
+        ]
+    );
     assert_eq!(to_sqlx.sql(), true_sql);
 }
 
@@ -460,7 +579,7 @@ fn test_sqlite_limit_offset() {
             qb.offset(20);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users LIMIT 20, 10";
     assert_eq!(sql.0, true_sql);
     assert_eq!(sql.1, Vec::<Value>::new());
@@ -478,7 +597,7 @@ fn test_sqlite_group_by() {
             qb.group_by(vec!["department".to_string(), "status".to_string()]);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users GROUP BY department, status";
     assert_eq!(sql.0, true_sql);
     assert_eq!(sql.1, Vec::<Value>::new());
@@ -496,7 +615,7 @@ fn test_sqlite_group_by_raw() {
             qb.group_by_raw("department, status", None);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users GROUP BY department, status";
     assert_eq!(sql.0, true_sql);
     assert_eq!(sql.1, Vec::<Value>::new());
@@ -515,7 +634,7 @@ fn test_sqlite_order_by() {
             qb.order_by("age", "DESC");
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users ORDER BY name ASC, age DESC";
     assert_eq!(sql.0, true_sql);
     assert_eq!(sql.1, Vec::<Value>::new());
@@ -533,7 +652,7 @@ fn test_sqlite_order_by_raw() {
             qb.order_by_raw("name ASC, age DESC", None);
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql = "SELECT * FROM mydb.users ORDER BY name ASC, age DESC";
     assert_eq!(sql.0, true_sql);
     assert_eq!(sql.1, Vec::<Value>::new());
@@ -554,7 +673,7 @@ fn test_sqlite_table_raw() {
             qb.where_eq("name", Value::String("John".to_string()));
         });
     let sql = builder.to_sql();
-    let to_sqlx = builder.to_sqlx_query();
+    let to_sqlx = builder.to_sqlx_query::<sqlx::Sqlite>();
     let true_sql =
         "SELECT * FROM (SELECT * FROM users WHERE status = ?) as active_users WHERE name = ?";
     assert_eq!(sql.0, true_sql);
@@ -661,6 +780,39 @@ fn test_sqlite_with_cte() {
     assert!(sql.contains("SELECT * FROM active_users"));
 }
 
+#[test]
+fn test_sqlite_nested_where_in_cte_and_union() {
+    // CTE and UNION bodies used to drop their WHERE clause and binds on SQLite
+    let mut recent = ChainBuilder::new(Client::Sqlite);
+    recent.table("orders").query(|qb| {
+        qb.where_gte("created_at", Value::from("2024-01-01"));
+    });
+    let mut archived = ChainBuilder::new(Client::Sqlite);
+    archived.table("archived_orders").query(|qb| {
+        qb.where_eq("status", Value::from("paid"));
+    });
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .with("recent", recent)
+        .table("recent")
+        .union_all(archived)
+        .query(|qb| qb.where_eq("status", Value::from("paid")));
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "WITH recent AS (SELECT * FROM orders WHERE created_at >= ?) SELECT * FROM recent WHERE status = ? UNION ALL SELECT * FROM archived_orders WHERE status = ?"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::from("2024-01-01"),
+            Value::from("paid"),
+            Value::from("paid")
+        ]
+    );
+}
+
 #[test]
 fn test_sqlite_union_old() {
     let mut pending_users = ChainBuilder::new(Client::Sqlite);
@@ -687,3 +839,199 @@ fn test_sqlite_union_old() {
     assert!(sql.contains("UNION"));
     assert!(sql.contains("SELECT * FROM users"));
 }
+
+#[test]
+fn test_to_sql_for_sqlite() {
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder
+        .db("mydb")
+        .select(Select::Columns(vec!["*".into()]))
+        .table("users")
+        .query(|qb| {
+            qb.where_eq("status", Value::String("active".to_string()));
+            qb.where_subquery(|sub| {
+                sub.where_eq("role", Value::String("admin".to_string()));
+                sub.or()
+                    .where_in("id", vec![Value::Number(1.into()), Value::Number(2.into())]);
+            });
+            qb.order_by_nulls("last_login", "desc", NullsOrder::Last);
+        });
+
+    let sqlite = builder.to_sql_for(Client::Sqlite);
+    assert_eq!(
+        sqlite.0,
+        "SELECT * FROM mydb.users WHERE status = ? AND (role = ? OR id IN (?,?)) ORDER BY last_login desc NULLS LAST"
+    );
+    assert_eq!(sqlite.1.len(), 4);
+
+    let compiled = builder.build_for(Client::Sqlite);
+    assert_eq!(compiled.sql, sqlite.0);
+    assert!(matches!(compiled.dialect, Client::Sqlite));
+
+    // the builder itself still compiles for MySQL
+    let mysql = builder.to_sql();
+    assert_ne!(mysql.0, sqlite.0);
+    assert!(matches!(builder.build().dialect, Client::Mysql));
+}
+
+#[test]
+fn test_to_sql_for_sqlite_nested_builders() {
+    // every nested builder below carries a MySQL client
+    let mut filter = QueryBuilder::default();
+    filter.where_expr(Expr::col("orders.total").gt(Expr::val(10)));
+
+    let mut builder = ChainBuilder::new(Client::Mysql);
+    builder.table("users").query(|qb| {
+        qb.where_exists(|sub| {
+            sub.table("orders").select_raw("1", None).query(|qb| {
+                qb.where_raw_named(
+                    r"orders.note <> 'a\' AND orders.user_id = :id",
+                    [("id", Value::from(7))],
+                );
+                qb.order_by_nulls("orders.created_at", "desc", NullsOrder::Last);
+                qb.limit(1);
+                qb.offset(2);
+                qb.merge(filter);
+            });
+        });
+    });
+
+    assert_eq!(
+        builder.to_sql_for(Client::Sqlite),
+        (
+            r#"SELECT * FROM users WHERE EXISTS (SELECT 1 FROM orders WHERE (orders.note <> 'a\' AND orders.user_id = ?) AND "orders"."total" > ? ORDER BY orders.created_at desc NULLS LAST LIMIT 2, 1)"#
+                .to_string(),
+            vec![Value::from(7), Value::from(10)]
+        )
+    );
+}
+
+#[test]
+fn test_sqlite_numbered_placeholders() {
+    // `?1` may be repeated, so the placeholder count is not checked against the binds
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("t").query(|qb| {
+        qb.where_raw("a = ?1 OR b = ?1", Some(vec![Value::from(1)]));
+    });
+    assert_eq!(
+        builder.to_sql(),
+        (
+            "SELECT * FROM t WHERE a = ?1 OR b = ?1".to_string(),
+            vec![Value::from(1)]
+        )
+    );
+}
+
+#[test]
+#[cfg(feature = "sqlx_sqlite")]
+fn test_push_to_sqlx_sqlite() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("orders").query(|qb| {
+        qb.where_eq("status", Value::from("paid"));
+        qb.where_eq("external_id", Value::from(u64::MAX));
+    });
+
+    let mut query_builder = sqlx::QueryBuilder::<sqlx::Sqlite>::new("SELECT COUNT(*) FROM (");
+    builder.push_to_sqlx(&mut query_builder);
+    query_builder.push(") o WHERE o.user_id = ");
+    query_builder.push_bind(7i64);
+    assert_eq!(
+        query_builder.sql(),
+        "SELECT COUNT(*) FROM (SELECT * FROM orders WHERE status = ? AND external_id = ?) o WHERE o.user_id = ?"
+    );
+
+    // u64 values above i64::MAX are bound as exact text
+    let mut expected = sqlx::sqlite::SqliteArguments::default();
+    sqlx::Arguments::add(&mut expected, "paid".to_string()).unwrap();
+    sqlx::Arguments::add(&mut expected, u64::MAX.to_string()).unwrap();
+    sqlx::Arguments::add(&mut expected, 7i64).unwrap();
+    let arguments = query_builder.build().take_arguments().unwrap().unwrap();
+    assert_eq!(format!("{:?}", arguments), format!("{:?}", expected));
+}
+
+#[test]
+#[cfg(feature = "sqlx_sqlite")]
+fn test_explain_borrows_builder() {
+    // explain only needs `&self`: the query is compiled for the pool's database
+    fn explain<'a>(
+        builder: &'a ChainBuilder,
+        pool: &'a sqlx::SqlitePool,
+    ) -> impl std::future::Future<Output = Result<QueryPlan, sqlx::Error>> + 'a {
+        builder.explain(pool)
+    }
+    let _ = explain;
+}
+
+#[test]
+fn test_in_list_threshold_sqlite() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("users").in_list_threshold(2).query(|qb| {
+        qb.where_in(
+            "id",
+            vec![Value::from(1), Value::from("two"), Value::from(3)],
+        );
+        qb.where_not_in("status", vec![Value::from("banned")]);
+    });
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE id IN (SELECT value FROM json_each(?)) AND status NOT IN (?)"
+    );
+    assert_eq!(
+        binds,
+        vec![
+            Value::String(r#"[1,"two",3]"#.to_string()),
+            Value::from("banned")
+        ]
+    );
+
+    // the threshold follows the builder to another dialect
+    let (sql, _) = builder.to_sql_for(Client::Mysql);
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE id IN (?,?,?) AND status NOT IN (?)"
+    );
+}
+
+#[test]
+fn test_in_list_threshold_keeps_template_slots() {
+    // a slot inside the list would be serialized into the JSON bind, so it stays inline
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder.table("users").in_list_threshold(1).query(|qb| {
+        qb.where_in("id", vec![Param("first").into(), Value::from(2)]);
+    });
+    let template = builder.to_template();
+    assert_eq!(template.sql(), "SELECT * FROM users WHERE id IN (?,?)");
+    assert_eq!(template.params(), vec!["first"]);
+    let (_, binds) = template.bind([("first", Value::from(1))]);
+    assert_eq!(binds, vec![Value::from(1), Value::from(2)]);
+}
+
+#[test]
+fn test_sqlite_insert_many_empty_rows() {
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("users")
+        .insert_many(vec![serde_json::json!({}), serde_json::json!({})]);
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT INTO users DEFAULT VALUES; INSERT INTO users DEFAULT VALUES"
+    );
+    assert!(binds.is_empty());
+
+    let mut builder = ChainBuilder::new(Client::Sqlite);
+    builder
+        .table("users")
+        .insert_many(vec![
+            serde_json::json!({ "name": "John" }),
+            serde_json::json!({}),
+        ])
+        .missing_column(MissingColumn::Default);
+    let (sql, binds) = builder.to_sql();
+    assert_eq!(
+        sql,
+        "INSERT INTO users (name) VALUES (?); INSERT INTO users DEFAULT VALUES"
+    );
+    assert_eq!(binds, vec![Value::from("John")]);
+}